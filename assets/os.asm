@@ -0,0 +1,693 @@
+; Minimal LC-3 operating system bundled with the emulator.
+;
+; It provides the standard trap service routines (GETC, OUT, PUTS, IN, PUTSP
+; and HALT) on top of the memory mapped keyboard, display and machine control
+; registers, plus handlers for the privilege mode violation and illegal opcode
+; exceptions. Service routines are entered in supervisor mode and return with
+; RTI. Unused trap vectors and interrupt vectors point to default handlers.
+
+        .ORIG x0000
+
+; trap vector table (x0000 - x00FF)
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_GETC      ; x20
+        .FILL TRAP_OUT      ; x21
+        .FILL TRAP_PUTS      ; x22
+        .FILL TRAP_IN      ; x23
+        .FILL TRAP_PUTSP      ; x24
+        .FILL TRAP_HALT      ; x25
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+        .FILL TRAP_BAD
+
+; interrupt vector table (x0100 - x01FF)
+        .FILL EXC_PRIVILEGE      ; x00
+        .FILL EXC_ILLEGAL_OPCODE      ; x01
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+        .FILL INT_BAD
+
+; GETC: read a single character from the keyboard into R0
+TRAP_GETC
+        LDI R0, OS_KBSR
+        BRzp TRAP_GETC
+        LDI R0, OS_KBDR
+        RTI
+
+; OUT: write the character in R0 to the display
+TRAP_OUT
+        ADD R6, R6, #-1
+        STR R1, R6, #0
+OUT_WAIT
+        LDI R1, OS_DSR
+        BRzp OUT_WAIT
+        STI R0, OS_DDR
+        LDR R1, R6, #0
+        ADD R6, R6, #1
+        RTI
+
+; PUTS: write the zero terminated string starting at R0
+TRAP_PUTS
+        ADD R6, R6, #-1
+        STR R0, R6, #0
+        ADD R6, R6, #-1
+        STR R1, R6, #0
+        ADD R6, R6, #-1
+        STR R2, R6, #0
+        ADD R1, R0, #0
+PUTS_LOOP
+        LDR R0, R1, #0
+        BRz PUTS_DONE
+PUTS_WAIT
+        LDI R2, OS_DSR
+        BRzp PUTS_WAIT
+        STI R0, OS_DDR
+        ADD R1, R1, #1
+        BRnzp PUTS_LOOP
+PUTS_DONE
+        LDR R2, R6, #0
+        ADD R6, R6, #1
+        LDR R1, R6, #0
+        ADD R6, R6, #1
+        LDR R0, R6, #0
+        ADD R6, R6, #1
+        RTI
+
+; IN: prompt for a character, echo it and return it in R0
+TRAP_IN
+        ADD R6, R6, #-1
+        STR R1, R6, #0
+        LEA R0, IN_PROMPT
+        TRAP x22
+IN_READ
+        LDI R0, OS_KBSR
+        BRzp IN_READ
+        LDI R0, OS_KBDR
+        ADD R1, R0, #0
+        TRAP x21
+        AND R0, R0, #0
+        ADD R0, R0, #10
+        TRAP x21
+        ADD R0, R1, #0
+        LDR R1, R6, #0
+        ADD R6, R6, #1
+        RTI
+
+; PUTSP: write the zero terminated string of packed characters starting at
+; R0, low byte first
+TRAP_PUTSP
+        ADD R6, R6, #-1
+        STR R1, R6, #0
+        ADD R6, R6, #-1
+        STR R2, R6, #0
+        ADD R6, R6, #-1
+        STR R3, R6, #0
+        ADD R6, R6, #-1
+        STR R4, R6, #0
+        ADD R6, R6, #-1
+        STR R5, R6, #0
+        ADD R6, R6, #-1
+        STR R0, R6, #0
+        ADD R1, R0, #0
+PUTSP_LOOP
+        LDR R3, R1, #0
+        BRz PUTSP_DONE
+        LD R2, LOW_BYTE
+        AND R0, R3, R2
+        BRz PUTSP_DONE
+        TRAP x21
+        ; shift the high byte down, one bit at a time
+        AND R0, R0, #0
+        LD R2, BIT_8
+        AND R4, R4, #0
+        ADD R4, R4, #1
+PUTSP_SHIFT
+        AND R5, R3, R2
+        BRz PUTSP_NEXT_BIT
+        ADD R0, R0, R4
+PUTSP_NEXT_BIT
+        ADD R4, R4, R4
+        ADD R2, R2, R2
+        BRnp PUTSP_SHIFT
+        ADD R0, R0, #0
+        BRz PUTSP_DONE
+        TRAP x21
+        ADD R1, R1, #1
+        BRnzp PUTSP_LOOP
+PUTSP_DONE
+        LDR R0, R6, #0
+        ADD R6, R6, #1
+        LDR R5, R6, #0
+        ADD R6, R6, #1
+        LDR R4, R6, #0
+        ADD R6, R6, #1
+        LDR R3, R6, #0
+        ADD R6, R6, #1
+        LDR R2, R6, #0
+        ADD R6, R6, #1
+        LDR R1, R6, #0
+        ADD R6, R6, #1
+        RTI
+
+; HALT: announce the halt and stop the clock
+TRAP_HALT
+        LEA R0, HALT_MESSAGE
+        TRAP x22
+HALT_CLOCK
+        LDI R1, OS_MCR
+        LD R0, CLOCK_MASK
+        AND R0, R1, R0
+        STI R0, OS_MCR
+        BRnzp HALT_CLOCK
+
+TRAP_BAD
+        LEA R0, BAD_TRAP_MESSAGE
+        TRAP x22
+        TRAP x25
+
+EXC_PRIVILEGE
+        LEA R0, PRIVILEGE_MESSAGE
+        TRAP x22
+        TRAP x25
+
+EXC_ILLEGAL_OPCODE
+        LEA R0, ILLEGAL_OPCODE_MESSAGE
+        TRAP x22
+        TRAP x25
+
+INT_BAD
+        RTI
+
+OS_KBSR .FILL xFE00
+OS_KBDR .FILL xFE02
+OS_DSR  .FILL xFE04
+OS_DDR  .FILL xFE06
+OS_MCR  .FILL xFFFE
+LOW_BYTE .FILL x00FF
+BIT_8   .FILL x0100
+CLOCK_MASK .FILL x7FFF
+
+IN_PROMPT .STRINGZ "\nInput a character> "
+HALT_MESSAGE .STRINGZ "\n--- Halting the LC-3 ---\n"
+BAD_TRAP_MESSAGE .STRINGZ "\n--- Undefined trap executed ---\n"
+PRIVILEGE_MESSAGE .STRINGZ "\n--- Privilege mode violation ---\n"
+ILLEGAL_OPCODE_MESSAGE .STRINGZ "\n--- Illegal opcode ---\n"
+
+        .END
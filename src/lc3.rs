@@ -1,5 +1,23 @@
+pub mod asm;
+pub mod console;
+pub mod dap;
+pub mod debugger;
+pub mod device;
+pub mod disasm;
+pub mod display;
 pub mod error;
+pub mod gdbstub;
+pub mod history;
 pub mod instructions;
+pub mod interrupt;
+pub mod keyboard;
 pub mod machine;
+pub mod machine_control;
 pub mod memory;
+pub mod os;
+pub mod profiler;
 pub mod registers;
+pub mod replay;
+pub mod snapshot;
+pub mod stats;
+pub mod trace;
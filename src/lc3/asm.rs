@@ -0,0 +1,791 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use super::{
+    instructions::{Instructions, JumpType, RegisterMode, TrapRoutine},
+    registers::RegistersEnum,
+};
+
+/// An error in the assembly source, located by 1-based line and column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(line: usize, column: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+#[derive(Debug, Default)]
+pub struct Program {
+    origin: u16,
+    words: Vec<u16>,
+    symbols: BTreeMap<String, u16>,
+    lines: Vec<usize>,
+}
+
+impl Program {
+    pub fn origin(&self) -> u16 {
+        self.origin
+    }
+
+    pub fn words(&self) -> &[u16] {
+        &self.words
+    }
+
+    pub fn symbols(&self) -> &BTreeMap<String, u16> {
+        &self.symbols
+    }
+
+    /// The source line the word at `address` was assembled from.
+    pub fn line(&self, address: u16) -> Option<usize> {
+        let index = address.wrapping_sub(self.origin) as usize;
+        self.lines.get(index).copied()
+    }
+
+    /// The object format `LittleComputer3::load_program` reads: the origin
+    /// followed by every word, all big endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        std::iter::once(self.origin)
+            .chain(self.words.iter().copied())
+            .flat_map(u16::to_be_bytes)
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+struct Token {
+    text: String,
+    column: usize,
+    quoted: bool,
+}
+
+#[derive(Debug)]
+struct Statement {
+    line: usize,
+    label: Option<Token>,
+    operation: Option<Token>,
+    operands: Vec<Token>,
+}
+
+const OPERATIONS: [&str; 26] = [
+    "ADD", "AND", "NOT", "JMP", "RET", "JSR", "JSRR", "LD", "LDI", "LDR", "LEA", "ST", "STI",
+    "STR", "TRAP", "RTI", "GETC", "OUT", "PUTS", "IN", "PUTSP", "HALT", ".ORIG", ".FILL", ".BLKW",
+    ".STRINGZ",
+];
+
+fn branch_condition(mnemonic: &str) -> Option<u16> {
+    let flags = mnemonic.strip_prefix("BR")?;
+    if flags.is_empty() {
+        return Some(0x7);
+    }
+
+    let mut condition = 0;
+    let mut rest = flags;
+    for (flag, bit) in [('N', 0x4), ('Z', 0x2), ('P', 0x1)] {
+        if let Some(stripped) = rest.strip_prefix(flag) {
+            condition |= bit;
+            rest = stripped;
+        }
+    }
+    rest.is_empty().then_some(condition)
+}
+
+fn is_operation(text: &str) -> bool {
+    let mnemonic = text.to_ascii_uppercase();
+    OPERATIONS.contains(&mnemonic.as_str())
+        || mnemonic == ".END"
+        || branch_condition(&mnemonic).is_some()
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut characters = text.chars();
+    matches!(characters.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && characters.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+pub(crate) fn parse_number(text: &str) -> Option<i32> {
+    let (radix, digits) = match text.as_bytes().first()? {
+        b'#' => (10, &text[1..]),
+        b'x' | b'X' => (16, &text[1..]),
+        b'b' | b'B' => (2, &text[1..]),
+        b'0' if text.len() > 2 && matches!(&text[1..2], "x" | "X") => (16, &text[2..]),
+        b'0' if text.len() > 2 && matches!(&text[1..2], "b" | "B") => (2, &text[2..]),
+        _ => (10, text),
+    };
+    let (negative, digits) = match digits.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, digits),
+    };
+    if digits.is_empty() || digits.starts_with(['+', '-']) {
+        return None;
+    }
+
+    let value = i32::from_str_radix(digits, radix).ok()?;
+    Some(if negative { -value } else { value })
+}
+
+fn tokenize(line: usize, source: &str) -> Result<Vec<Token>, Diagnostic> {
+    let mut tokens = Vec::new();
+    let mut characters = source.chars().enumerate().peekable();
+    while let Some((index, character)) = characters.next() {
+        let column = index + 1;
+        match character {
+            ';' => break,
+            c if c.is_whitespace() || c == ',' => {}
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    let Some((_, character)) = characters.next() else {
+                        return Err(Diagnostic::new(line, column, "unterminated string"));
+                    };
+                    match character {
+                        '"' => break,
+                        '\\' => {
+                            let Some((index, escaped)) = characters.next() else {
+                                return Err(Diagnostic::new(line, column, "unterminated string"));
+                            };
+                            text.push(match escaped {
+                                'n' => '\n',
+                                'r' => '\r',
+                                't' => '\t',
+                                'e' => '\x1B',
+                                '0' => '\0',
+                                '\\' | '"' => escaped,
+                                _ => {
+                                    return Err(Diagnostic::new(
+                                        line,
+                                        index + 1,
+                                        format!("unknown escape sequence '\\{escaped}'"),
+                                    ))
+                                }
+                            });
+                        }
+                        _ => text.push(character),
+                    }
+                }
+                tokens.push(Token {
+                    text,
+                    column,
+                    quoted: true,
+                });
+            }
+            _ => {
+                let mut text = String::from(character);
+                while let Some((_, next)) = characters.peek() {
+                    if next.is_whitespace() || matches!(next, ',' | ';' | '"') {
+                        break;
+                    }
+                    text.push(*next);
+                    characters.next();
+                }
+                tokens.push(Token {
+                    text,
+                    column,
+                    quoted: false,
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_statement(line: usize, source: &str) -> Result<Statement, Diagnostic> {
+    let mut tokens = tokenize(line, source)?.into_iter().peekable();
+    let mut statement = Statement {
+        line,
+        label: None,
+        operation: None,
+        operands: Vec::new(),
+    };
+
+    if let Some(token) = tokens.next_if(|token| !token.quoted && !is_operation(&token.text)) {
+        let name = token.text.strip_suffix(':').unwrap_or(&token.text);
+        if !is_identifier(name) || parse_number(name).is_some() {
+            return Err(Diagnostic::new(
+                line,
+                token.column,
+                format!("'{}' is not a valid label", token.text),
+            ));
+        }
+        statement.label = Some(Token {
+            text: name.to_string(),
+            ..token
+        });
+    }
+
+    if let Some(token) = tokens.next() {
+        if token.quoted || !is_operation(&token.text) {
+            return Err(Diagnostic::new(
+                line,
+                token.column,
+                format!("'{}' is not a known instruction", token.text),
+            ));
+        }
+        statement.operation = Some(Token {
+            text: token.text.to_ascii_uppercase(),
+            ..token
+        });
+    }
+
+    statement.operands = tokens.collect();
+    Ok(statement)
+}
+
+struct Encoder<'a> {
+    statement: &'a Statement,
+    operation: &'a Token,
+    address: u16,
+    symbols: &'a BTreeMap<String, u16>,
+}
+
+impl Encoder<'_> {
+    fn error(&self, token: &Token, message: impl Into<String>) -> Diagnostic {
+        Diagnostic::new(self.statement.line, token.column, message)
+    }
+
+    fn operands<const N: usize>(&self) -> Result<&[Token; N], Diagnostic> {
+        let operands = self.statement.operands.as_slice();
+        operands.try_into().map_err(|_| {
+            self.error(
+                self.operation,
+                format!(
+                    "'{}' expects {N} operand{}, found {}",
+                    self.operation.text,
+                    if N == 1 { "" } else { "s" },
+                    operands.len()
+                ),
+            )
+        })
+    }
+
+    fn register(&self, token: &Token) -> Result<RegistersEnum, Diagnostic> {
+        match token.text.as_bytes() {
+            [b'R' | b'r', digit @ b'0'..=b'7'] if !token.quoted => {
+                Ok(((digit - b'0') as u16).try_into().expect("R0 to R7"))
+            }
+            _ => Err(self.error(
+                token,
+                format!("expected a register, found '{}'", token.text),
+            )),
+        }
+    }
+
+    fn number(&self, token: &Token) -> Result<i32, Diagnostic> {
+        parse_number(&token.text)
+            .filter(|_| !token.quoted)
+            .ok_or_else(|| self.error(token, format!("expected a number, found '{}'", token.text)))
+    }
+
+    fn fit(&self, token: &Token, value: i32, min: i32, max: i32) -> Result<i32, Diagnostic> {
+        if value < min || value > max {
+            return Err(self.error(
+                token,
+                format!("{value} is out of range, expected {min} to {max}"),
+            ));
+        }
+        Ok(value)
+    }
+
+    fn signed(&self, token: &Token, bits: u32) -> Result<u16, Diagnostic> {
+        let limit = 1 << (bits - 1);
+        let value = self.fit(token, self.number(token)?, -limit, limit - 1)?;
+        Ok(value as u16)
+    }
+
+    /// A label or a literal offset relative to the incremented PC.
+    fn pc_offset(&self, token: &Token, bits: u32) -> Result<u16, Diagnostic> {
+        if parse_number(&token.text).is_some() {
+            return self.signed(token, bits);
+        }
+
+        let target = self.label(token)?;
+        let offset = target as i32 - (self.address as i32 + 1);
+        let limit = 1 << (bits - 1);
+        if offset < -limit || offset >= limit {
+            return Err(self.error(
+                token,
+                format!(
+                    "'{}' is {offset} words away, which does not fit in a {bits} bit offset",
+                    token.text
+                ),
+            ));
+        }
+        Ok(offset as u16)
+    }
+
+    fn label(&self, token: &Token) -> Result<u16, Diagnostic> {
+        self.symbols
+            .get(token.text.strip_suffix(':').unwrap_or(&token.text))
+            .copied()
+            .filter(|_| !token.quoted)
+            .ok_or_else(|| self.error(token, format!("'{}' is not a defined label", token.text)))
+    }
+
+    fn encode(&self) -> Result<Vec<u16>, Diagnostic> {
+        let mnemonic = self.operation.text.as_str();
+        let instruction = match mnemonic {
+            "ADD" | "AND" => {
+                let [destination, source1, source2] = self.operands::<3>()?;
+                let destination = self.register(destination)?;
+                let source1 = self.register(source1)?;
+                let source2 = match self.register(source2) {
+                    Ok(register) => RegisterMode::Register(register),
+                    Err(_) if parse_number(&source2.text).is_some() => {
+                        RegisterMode::Immediate(self.signed(source2, 5)?)
+                    }
+                    Err(error) => return Err(error),
+                };
+                if mnemonic == "ADD" {
+                    Instructions::Add {
+                        destination,
+                        source1,
+                        source2,
+                    }
+                } else {
+                    Instructions::And {
+                        destination,
+                        source1,
+                        source2,
+                    }
+                }
+            }
+            "NOT" => {
+                let [destination, source] = self.operands::<2>()?;
+                Instructions::Not {
+                    destination: self.register(destination)?,
+                    source1: self.register(source)?,
+                }
+            }
+            "JMP" => {
+                let [base] = self.operands::<1>()?;
+                Instructions::Jump {
+                    source: self.register(base)?,
+                }
+            }
+            "RET" => {
+                self.operands::<0>()?;
+                Instructions::Jump {
+                    source: RegistersEnum::R7,
+                }
+            }
+            "JSR" => {
+                let [target] = self.operands::<1>()?;
+                Instructions::JumpRegister(JumpType::Long(self.pc_offset(target, 11)?))
+            }
+            "JSRR" => {
+                let [base] = self.operands::<1>()?;
+                Instructions::JumpRegister(JumpType::Register(self.register(base)?))
+            }
+            "LD" | "LDI" | "LEA" => {
+                let [register, target] = self.operands::<2>()?;
+                let destination = self.register(register)?;
+                let pc_offset = self.pc_offset(target, 9)?;
+                match mnemonic {
+                    "LD" => Instructions::Load {
+                        destination,
+                        pc_offset,
+                    },
+                    "LDI" => Instructions::LoadIndirect {
+                        destination,
+                        pc_offset,
+                    },
+                    _ => Instructions::LoadEffectiveAddress {
+                        destination,
+                        pc_offset,
+                    },
+                }
+            }
+            "ST" | "STI" => {
+                let [register, target] = self.operands::<2>()?;
+                let source = self.register(register)?;
+                let pc_offset = self.pc_offset(target, 9)?;
+                if mnemonic == "ST" {
+                    Instructions::Store { source, pc_offset }
+                } else {
+                    Instructions::StoreIndirect { source, pc_offset }
+                }
+            }
+            "LDR" => {
+                let [register, base, offset] = self.operands::<3>()?;
+                Instructions::LoadRegister {
+                    destination: self.register(register)?,
+                    source1: self.register(base)?,
+                    offset: self.signed(offset, 6)?,
+                }
+            }
+            "STR" => {
+                let [register, base, offset] = self.operands::<3>()?;
+                Instructions::StoreRegister {
+                    source1: self.register(register)?,
+                    source2: self.register(base)?,
+                    offset: self.signed(offset, 6)?,
+                }
+            }
+            "TRAP" => {
+                let [vector] = self.operands::<1>()?;
+                let vector = self.fit(vector, self.number(vector)?, 0, 0xFF)? as u16;
+                Instructions::Trap(vector.try_into().expect("a vector below x100"))
+            }
+            "RTI" => {
+                self.operands::<0>()?;
+                Instructions::RTI
+            }
+            "GETC" | "OUT" | "PUTS" | "IN" | "PUTSP" | "HALT" => {
+                self.operands::<0>()?;
+                Instructions::Trap(match mnemonic {
+                    "GETC" => TrapRoutine::GETC,
+                    "OUT" => TrapRoutine::OUT,
+                    "PUTS" => TrapRoutine::PUTS,
+                    "IN" => TrapRoutine::IN,
+                    "PUTSP" => TrapRoutine::PUTSP,
+                    _ => TrapRoutine::HALT,
+                })
+            }
+            ".FILL" => {
+                let [value] = self.operands::<1>()?;
+                let word = match parse_number(&value.text) {
+                    Some(number) => {
+                        self.fit(value, number, i16::MIN as i32, u16::MAX as i32)? as u16
+                    }
+                    None => self.label(value)?,
+                };
+                return Ok(vec![word]);
+            }
+            ".BLKW" => return Ok(vec![0; self.block_size()? as usize]),
+            ".STRINGZ" => {
+                let [text] = self.operands::<1>()?;
+                if !text.quoted {
+                    return Err(
+                        self.error(text, format!("expected a string, found '{}'", text.text))
+                    );
+                }
+                return Ok(text.text.chars().map(|c| c as u16).chain([0]).collect());
+            }
+            _ => {
+                let condition = branch_condition(mnemonic).expect("a known operation");
+                let [target] = self.operands::<1>()?;
+                Instructions::Branch {
+                    pc_offset: self.pc_offset(target, 9)?,
+                    condition_flag: condition,
+                }
+            }
+        };
+
+        let word = instruction
+            .encode()
+            .map_err(|error| self.error(self.operation, error.to_string()))?;
+        Ok(vec![word])
+    }
+
+    fn block_size(&self) -> Result<u16, Diagnostic> {
+        let [count] = self.operands::<1>()?;
+        Ok(self.fit(count, self.number(count)?, 1, u16::MAX as i32)? as u16)
+    }
+
+    fn origin(&self) -> Result<u16, Diagnostic> {
+        let [start] = self.operands::<1>()?;
+        Ok(self.fit(start, self.number(start)?, 0, u16::MAX as i32)? as u16)
+    }
+
+    fn size(&self) -> Result<u16, Diagnostic> {
+        match self.operation.text.as_str() {
+            ".BLKW" => self.block_size(),
+            ".STRINGZ" => match self.statement.operands.as_slice() {
+                [text] if text.quoted => Ok(text.text.chars().count() as u16 + 1),
+                _ => Ok(1),
+            },
+            _ => Ok(1),
+        }
+    }
+}
+
+/// Assembles LC-3 assembly into a program, reporting every error found.
+pub fn assemble(source: &str) -> Result<Program, Vec<Diagnostic>> {
+    let mut diagnostics = Vec::new();
+    let mut program = Program::default();
+    let mut origin = None;
+    let mut address = 0u32;
+    let mut defined = BTreeMap::new();
+    let mut placed = Vec::new();
+
+    for (index, text) in source.lines().enumerate() {
+        let statement = match parse_statement(index + 1, text) {
+            Ok(statement) => statement,
+            Err(diagnostic) => {
+                diagnostics.push(diagnostic);
+                continue;
+            }
+        };
+        let line = statement.line;
+
+        if let Some(label) = &statement.label {
+            if origin.is_none() {
+                diagnostics.push(Diagnostic::new(
+                    line,
+                    label.column,
+                    "labels must follow .ORIG",
+                ));
+            } else if let Some(previous) = defined.insert(label.text.clone(), line) {
+                diagnostics.push(Diagnostic::new(
+                    line,
+                    label.column,
+                    format!("'{}' is already defined on line {previous}", label.text),
+                ));
+            } else {
+                program.symbols.insert(label.text.clone(), address as u16);
+            }
+        }
+
+        let Some(operation) = &statement.operation else {
+            continue;
+        };
+        match (operation.text.as_str(), origin) {
+            (".END", _) => break,
+            (".ORIG", None) => {
+                let encoder = Encoder {
+                    statement: &statement,
+                    operation,
+                    address: 0,
+                    symbols: &program.symbols,
+                };
+                match encoder.origin() {
+                    Ok(start) => {
+                        origin = Some(start);
+                        address = start as u32;
+                    }
+                    Err(diagnostic) => {
+                        diagnostics.push(diagnostic);
+                        origin = Some(0);
+                    }
+                }
+                continue;
+            }
+            (".ORIG", Some(_)) => {
+                diagnostics.push(Diagnostic::new(
+                    line,
+                    operation.column,
+                    "only one .ORIG is supported",
+                ));
+                continue;
+            }
+            (_, None) => {
+                diagnostics.push(Diagnostic::new(
+                    line,
+                    operation.column,
+                    "expected .ORIG before the first instruction",
+                ));
+                origin = Some(0);
+            }
+            _ => {}
+        }
+
+        let encoder = Encoder {
+            statement: &statement,
+            operation,
+            address: address as u16,
+            symbols: &program.symbols,
+        };
+        let size = encoder.size().unwrap_or(1);
+        if address + size as u32 > 0x10000 {
+            diagnostics.push(Diagnostic::new(
+                line,
+                operation.column,
+                "the program does not fit below xFFFF",
+            ));
+            break;
+        }
+        placed.push((address as u16, statement));
+        address += size as u32;
+    }
+
+    for (address, statement) in &placed {
+        let encoder = Encoder {
+            statement,
+            operation: statement
+                .operation
+                .as_ref()
+                .expect("placed statements have an operation"),
+            address: *address,
+            symbols: &program.symbols,
+        };
+        match encoder.encode() {
+            Ok(words) => {
+                program.lines.extend(words.iter().map(|_| statement.line));
+                program.words.extend(words);
+            }
+            Err(diagnostic) => diagnostics.push(diagnostic),
+        }
+    }
+
+    if origin.is_none() && diagnostics.is_empty() {
+        diagnostics.push(Diagnostic::new(1, 1, "the program has no .ORIG"));
+    }
+
+    if diagnostics.is_empty() {
+        program.origin = origin.unwrap_or_default();
+        Ok(program)
+    } else {
+        diagnostics.sort_by_key(|diagnostic| (diagnostic.line, diagnostic.column));
+        Err(diagnostics)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{assemble, Diagnostic};
+
+    fn words(source: &str) -> Vec<u16> {
+        assemble(source).unwrap().words().to_vec()
+    }
+
+    #[test]
+    fn test_os_image() {
+        let program = assemble(include_str!("../../assets/os.asm")).unwrap();
+        assert_eq!(crate::lc3::os::OS_IMAGE, program.to_bytes().as_slice());
+    }
+
+    #[test]
+    fn test_operations() {
+        let source = "
+            .ORIG x3000
+        START
+            ADD R0, R1, R2
+            ADD R0, R1, #-16
+            AND R3, R4, x0F
+            NOT R5, R6
+            BR START
+            BRnz START
+            JMP R2
+            RET
+            JSR START
+            JSRR R3
+            LD R1, START
+            LDI R1, #-1
+            LDR R1, R2, #31
+            LEA R7, START
+            ST R1, START
+            STI R1, START
+            STR R1, R2, #-32
+            TRAP x26
+            RTI
+            GETC
+            OUT
+            PUTS
+            IN
+            PUTSP
+            HALT
+            .END
+            ADD R0, R0, R0
+        ";
+        assert_eq!(
+            vec![
+                0x1042, 0x1070, 0x572F, 0x9BBF, 0x0FFB, 0x0DFA, 0xC080, 0xC1C0, 0x4FF7, 0x40C0,
+                0x23F5, 0xA3FF, 0x629F, 0xEFF2, 0x33F1, 0xB3F0, 0x72A0, 0xF026, 0x8000, 0xF020,
+                0xF021, 0xF022, 0xF023, 0xF024, 0xF025,
+            ],
+            words(source)
+        );
+    }
+
+    #[test]
+    fn test_pseudo_ops() {
+        let source = r#"
+            .ORIG 0x4000
+            .FILL #-1
+            .FILL b101
+            .FILL 0b11
+            .FILL 42
+            .FILL DATA
+            .BLKW 2
+        DATA: .STRINGZ "a\n\"b"
+        "#;
+        let program = assemble(source).unwrap();
+        assert_eq!(0x4000, program.origin());
+        assert_eq!(Some(&0x4007), program.symbols().get("DATA"));
+        assert_eq!(
+            vec![0xFFFF, 5, 3, 42, 0x4007, 0, 0, 0x61, 0x0A, 0x22, 0x62, 0],
+            program.words()
+        );
+        assert_eq!(Some(9), program.line(0x4007));
+        assert_eq!(
+            vec![0x40, 0x00, 0xFF, 0xFF, 0x00, 0x05],
+            program.to_bytes()[..6].to_vec()
+        );
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let source = "  .ORIG x3000
+  ADD R0, R1, #16
+  LD R9, FAR
+  BRnz MISSING
+  FOO R1
+  .BLKW x200
+FAR .STRINGZ \"unterminated
+  HALT R0
+";
+        let diagnostics = assemble(source).unwrap_err();
+        assert_eq!(
+            vec![
+                Diagnostic::new(2, 15, "16 is out of range, expected -16 to 15"),
+                Diagnostic::new(3, 6, "expected a register, found 'R9'"),
+                Diagnostic::new(4, 8, "'MISSING' is not a defined label"),
+                Diagnostic::new(5, 7, "'R1' is not a known instruction"),
+                Diagnostic::new(7, 14, "unterminated string"),
+                Diagnostic::new(8, 3, "'HALT' expects 0 operands, found 1"),
+            ],
+            diagnostics
+        );
+    }
+
+    #[test]
+    fn test_offset_range() {
+        let source = "
+            .ORIG x3000
+            LD R0, FAR
+            .BLKW 256
+        FAR .FILL 0
+            LD R0, FAR
+        ";
+        let diagnostics = assemble(source).unwrap_err();
+        assert_eq!(1, diagnostics.len());
+        assert_eq!((3, 20), (diagnostics[0].line, diagnostics[0].column));
+        assert!(diagnostics[0].message.contains("256 words away"));
+    }
+
+    #[test]
+    fn test_structure_errors() {
+        assert_eq!(
+            "1:1: the program has no .ORIG",
+            assemble("; nothing").unwrap_err()[0].to_string()
+        );
+        assert_eq!(
+            "1:1: expected .ORIG before the first instruction",
+            assemble("HALT").unwrap_err()[0].to_string()
+        );
+        assert_eq!(
+            "3:1: 'A' is already defined on line 2",
+            assemble(".ORIG x3000\nA HALT\nA HALT").unwrap_err()[0].to_string()
+        );
+        assert_eq!(
+            "3:2: the program does not fit below xFFFF",
+            assemble(".ORIG xFFFF\n HALT\n HALT").unwrap_err()[0].to_string()
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use std::{
+    cell::{Ref, RefCell, RefMut},
+    io::{ErrorKind, Read, Write},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+/// Shared handle to a stream, so one `Read + Write` object such as a socket
+/// can serve as both input and output of a machine, or so output written by
+/// the machine can be inspected afterwards.
+#[derive(Debug, Default)]
+pub struct Shared<T>(Rc<RefCell<T>>);
+
+impl<T> Shared<T> {
+    pub fn new(inner: T) -> Self {
+        Self(Rc::new(RefCell::new(inner)))
+    }
+
+    pub fn borrow(&self) -> Ref<'_, T> {
+        self.0.borrow()
+    }
+
+    pub fn borrow_mut(&self) -> RefMut<'_, T> {
+        self.0.borrow_mut()
+    }
+}
+
+impl<T> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: Read> Read for Shared<T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().read(buf)
+    }
+}
+
+impl<T: Write> Write for Shared<T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.borrow_mut().flush()
+    }
+}
+
+/// Reads from a blocking stream, such as stdin, on a background thread.
+/// Reads return [`ErrorKind::WouldBlock`] while no input is available, so
+/// the keyboard can be polled without stalling the machine.
+pub struct NonBlockingReader {
+    receiver: Receiver<u8>,
+}
+
+impl NonBlockingReader {
+    pub fn new<R>(mut reader: R) -> Self
+    where
+        R: Read + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buffer = [0u8; 1];
+            loop {
+                match reader.read(&mut buffer) {
+                    Ok(0) => break,
+                    Ok(_) => {
+                        if sender.send(buffer[0]).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(_) => break,
+                }
+            }
+        });
+        Self { receiver }
+    }
+}
+
+impl Read for NonBlockingReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        match self.receiver.try_recv() {
+            Ok(byte) => {
+                buf[0] = byte;
+                let mut count = 1;
+                while count < buf.len() {
+                    match self.receiver.try_recv() {
+                        Ok(byte) => {
+                            buf[count] = byte;
+                            count += 1;
+                        }
+                        Err(_) => break,
+                    }
+                }
+                Ok(count)
+            }
+            Err(TryRecvError::Empty) => Err(ErrorKind::WouldBlock.into()),
+            Err(TryRecvError::Disconnected) => Ok(0),
+        }
+    }
+}
+
+/// Waits for a non-blocking stream, such as a [`NonBlockingReader`], to
+/// deliver input. With a `BufReader` of capacity 1 on top, lines can be read
+/// without taking any input past the end of the line.
+pub struct BlockingReader<R> {
+    reader: R,
+}
+
+impl<R: Read> BlockingReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader }
+    }
+}
+
+impl<R: Read> Read for BlockingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            match self.reader.read(buf) {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(1))
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Removes a hotkey, such as Ctrl-T, from a stream and records that it was
+/// pressed. It can sit below a [`NonBlockingReader`] so presses are noticed
+/// even while the program does not read the keyboard.
+pub struct HotkeyReader<R> {
+    reader: R,
+    key: u8,
+    hotkey: Hotkey,
+}
+
+/// Tells whether the key of a [`HotkeyReader`] was pressed. It can also be
+/// pressed from elsewhere, e.g. a signal handler.
+#[derive(Clone, Debug, Default)]
+pub struct Hotkey(Arc<AtomicBool>);
+
+impl Hotkey {
+    pub fn press(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_pressed(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Whether the key was pressed since the last call.
+    pub fn take(&self) -> bool {
+        self.0.swap(false, Ordering::Relaxed)
+    }
+}
+
+impl<R: Read> HotkeyReader<R> {
+    pub fn new(reader: R, key: u8) -> (Self, Hotkey) {
+        let hotkey = Hotkey::default();
+        let reader = Self {
+            reader,
+            key,
+            hotkey: hotkey.clone(),
+        };
+        (reader, hotkey)
+    }
+}
+
+impl<R: Read> Read for HotkeyReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let count = self.reader.read(buf)?;
+            if count == 0 {
+                return Ok(0);
+            }
+
+            let mut kept = 0;
+            for index in 0..count {
+                if buf[index] == self.key {
+                    self.hotkey.press();
+                } else {
+                    buf[kept] = buf[index];
+                    kept += 1;
+                }
+            }
+            if kept > 0 {
+                return Ok(kept);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{BufRead, BufReader, Cursor, ErrorKind, Read};
+
+    use super::{BlockingReader, HotkeyReader, NonBlockingReader, Shared};
+
+    #[test]
+    fn test_hotkey_reader() {
+        let (mut reader, hotkey) = HotkeyReader::new(Cursor::new(b"a\x14b\x14".to_vec()), 0x14);
+        let mut received = Vec::new();
+        reader.read_to_end(&mut received).unwrap();
+        assert_eq!(b"ab".to_vec(), received);
+        assert!(hotkey.take());
+        assert!(!hotkey.is_pressed());
+    }
+
+    #[test]
+    fn test_non_blocking_reader() {
+        let mut reader = NonBlockingReader::new(Cursor::new(b"hi".to_vec()));
+        let mut received = Vec::new();
+        let mut buffer = [0u8; 4];
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(count) => received.extend_from_slice(&buffer[..count]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => std::thread::yield_now(),
+                Err(e) => panic!("{e}"),
+            }
+        }
+        assert_eq!(b"hi".to_vec(), received);
+    }
+
+    #[test]
+    fn test_blocking_line_reader() {
+        let input = Shared::new(NonBlockingReader::new(Cursor::new(b"next\nab".to_vec())));
+        let mut commands = BufReader::with_capacity(1, BlockingReader::new(input.clone()));
+        let mut line = String::new();
+        commands.read_line(&mut line).unwrap();
+        assert_eq!("next\n", line);
+
+        let mut rest = Vec::new();
+        BlockingReader::new(input).read_to_end(&mut rest).unwrap();
+        assert_eq!(b"ab".to_vec(), rest);
+    }
+}
@@ -0,0 +1,817 @@
+use std::{
+    cell::Cell,
+    collections::{BTreeMap, BTreeSet},
+    io::{BufRead, PipeReader, PipeWriter, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+};
+
+use serde_json::{json, Value};
+
+use crate::vm::registers::RegistersTrait;
+
+use super::{
+    asm::{self, Program},
+    console::{Hotkey, NonBlockingReader, Shared},
+    debugger::frame_change,
+    error::Error,
+    machine::{LittleComputer3, RunOutcome},
+    registers::RegistersEnum,
+};
+
+const THREAD_ID: u64 = 1;
+const REGISTERS_REFERENCE: u64 = 1;
+const MEMORY_REFERENCE: u64 = 2;
+/// Words shown in the memory scope of programs without labels.
+const MEMORY_WINDOW: u16 = 16;
+
+const REGISTERS: [(&str, RegistersEnum); 10] = [
+    ("R0", RegistersEnum::R0),
+    ("R1", RegistersEnum::R1),
+    ("R2", RegistersEnum::R2),
+    ("R3", RegistersEnum::R3),
+    ("R4", RegistersEnum::R4),
+    ("R5", RegistersEnum::R5),
+    ("R6", RegistersEnum::R6),
+    ("R7", RegistersEnum::R7),
+    ("PC", RegistersEnum::ProgramCounter),
+    ("PSR", RegistersEnum::ProcessorStatus),
+];
+
+/// Serves the Debug Adapter Protocol on `input` and `output`, e.g. stdin and
+/// stdout, until the client disconnects.
+///
+/// Text entered in the debug console (`evaluate` requests in the `repl`
+/// context) is typed into the program's keyboard, one line at a time.
+/// Requests are answered while the program runs, even while a TRAP waits
+/// for a key.
+pub fn serve<I, O>(input: I, output: O) -> Result<(), Error>
+where
+    I: BufRead + Send + 'static,
+    O: Write + 'static,
+{
+    let (keyboard, typing) = std::io::pipe()?;
+    let pause = Hotkey::default();
+    let requests = spawn_reader(input, typing, pause.clone());
+    let mut server = DapServer {
+        sender: Sender {
+            output: Shared::new(output),
+            seq: Rc::new(Cell::new(0)),
+        },
+        keyboard: Some(keyboard),
+        pause,
+        machine: None,
+        program: None,
+        source: None,
+        breakpoints: BTreeSet::new(),
+        stop_on_entry: false,
+        mode: None,
+        events: Vec::new(),
+    };
+    server.run(requests)
+}
+
+/// Reads one message, `None` at the end of the input.
+fn read_message(input: &mut impl BufRead) -> std::io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() && length.is_some() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse().ok();
+        }
+    }
+
+    let mut body = vec![0; length.unwrap_or_default()];
+    input.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+/// Reads requests on a background thread, so they can arrive while the
+/// program runs. Console input is passed to the program straight away, as
+/// the program may be blocked waiting for it. Every request presses `pause`
+/// once it is queued, which stops a running machine to answer it.
+fn spawn_reader<I>(mut input: I, mut typing: PipeWriter, pause: Hotkey) -> Receiver<Value>
+where
+    I: BufRead + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        while let Ok(Some(message)) = read_message(&mut input) {
+            let arguments = &message["arguments"];
+            if message["command"] == "evaluate" && arguments["context"] == "repl" {
+                let text = arguments["expression"].as_str().unwrap_or_default();
+                if writeln!(typing, "{text}").is_err() {
+                    break;
+                }
+            }
+            if sender.send(message).is_err() {
+                break;
+            }
+            pause.press();
+        }
+    });
+    receiver
+}
+
+/// Writes messages with their `Content-Length` header and numbers them.
+struct Sender<O> {
+    output: Shared<O>,
+    seq: Rc<Cell<u64>>,
+}
+
+impl<O> Clone for Sender<O> {
+    fn clone(&self) -> Self {
+        Self {
+            output: self.output.clone(),
+            seq: self.seq.clone(),
+        }
+    }
+}
+
+impl<O: Write> Sender<O> {
+    fn send(&self, mut message: Value) -> std::io::Result<()> {
+        self.seq.set(self.seq.get() + 1);
+        message["seq"] = json!(self.seq.get());
+        let body = message.to_string();
+        let mut output = self.output.borrow_mut();
+        write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+        output.flush()
+    }
+
+    fn event(&self, event: &str, body: Value) -> std::io::Result<()> {
+        let mut message = json!({ "type": "event", "event": event });
+        if !body.is_null() {
+            message["body"] = body;
+        }
+        self.send(message)
+    }
+}
+
+/// Forwards the program's console output as `output` events.
+struct Console<O>(Sender<O>);
+
+impl<O: Write> Write for Console<O> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let output = String::from_utf8_lossy(buf);
+        self.0
+            .event("output", json!({ "category": "stdout", "output": output }))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// How far the program runs before it stops again.
+#[derive(Clone, Copy, Debug)]
+enum Mode {
+    Continue,
+    StepIn,
+    /// Steps over subroutines; `depth` counts the frames entered since.
+    Next {
+        depth: i32,
+    },
+    StepOut {
+        depth: i32,
+    },
+}
+
+struct DapServer<O> {
+    sender: Sender<O>,
+    keyboard: Option<PipeReader>,
+    /// Pressed by every request.
+    pause: Hotkey,
+    machine: Option<LittleComputer3>,
+    /// The assembled source, for its line table and labels.
+    program: Option<Program>,
+    source: Option<PathBuf>,
+    breakpoints: BTreeSet<u16>,
+    stop_on_entry: bool,
+    /// Set while the program runs.
+    mode: Option<Mode>,
+    /// Events to send once the response to the current request is out.
+    events: Vec<(&'static str, Value)>,
+}
+
+impl<O: Write + 'static> DapServer<O> {
+    fn run(&mut self, requests: Receiver<Value>) -> Result<(), Error> {
+        loop {
+            let request = if self.mode.is_some() {
+                match requests.try_recv() {
+                    Ok(request) => Some(request),
+                    Err(TryRecvError::Empty) => None,
+                    Err(TryRecvError::Disconnected) => return Ok(()),
+                }
+            } else {
+                match requests.recv() {
+                    Ok(request) => Some(request),
+                    Err(_) => return Ok(()),
+                }
+            };
+
+            match request {
+                Some(request) => {
+                    if !self.handle(&request)? {
+                        return Ok(());
+                    }
+                }
+                None => self.advance()?,
+            }
+        }
+    }
+
+    /// Answers `request`, returns `false` once the client disconnected.
+    fn handle(&mut self, request: &Value) -> Result<bool, Error> {
+        let command = request["command"].as_str().unwrap_or_default();
+        let arguments = &request["arguments"];
+        let result = match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsTerminateRequest": true,
+            })),
+            "launch" => self.launch(arguments),
+            "setBreakpoints" => self.set_breakpoints(arguments),
+            "setExceptionBreakpoints" => Ok(json!({ "breakpoints": [] })),
+            "configurationDone" => {
+                if self.stop_on_entry {
+                    self.stopped("entry");
+                } else {
+                    self.mode = Some(Mode::Continue);
+                }
+                Ok(Value::Null)
+            }
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "LC-3" }] })),
+            "stackTrace" => self.stack_trace(),
+            "scopes" => Ok(json!({ "scopes": [
+                { "name": "Registers", "variablesReference": REGISTERS_REFERENCE, "expensive": false },
+                { "name": "Memory", "variablesReference": MEMORY_REFERENCE, "expensive": false },
+            ] })),
+            "variables" => self.variables(arguments),
+            "continue" => self.resume(Mode::Continue),
+            "next" => self.resume(Mode::Next { depth: 0 }),
+            "stepIn" => self.resume(Mode::StepIn),
+            "stepOut" => self.resume(Mode::StepOut { depth: 0 }),
+            "pause" => {
+                if self.mode.take().is_some() {
+                    self.stopped("pause");
+                }
+                Ok(Value::Null)
+            }
+            "evaluate" => self.evaluate(arguments),
+            "disconnect" | "terminate" => Ok(Value::Null),
+            _ => Err(format!("unsupported request '{command}'")),
+        };
+
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": command,
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(Value::Null) => {}
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+        self.sender.send(response)?;
+        self.flush_events()?;
+
+        Ok(!matches!(command, "disconnect" | "terminate"))
+    }
+
+    fn launch(&mut self, arguments: &Value) -> Result<Value, String> {
+        if self.keyboard.is_none() {
+            return Err("a program was launched already".to_string());
+        }
+        let path = arguments["program"]
+            .as_str()
+            .ok_or("'program' names the .asm or .obj file to debug")?;
+
+        let image = if path.ends_with(".asm") {
+            let source = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+            let program = asm::assemble(&source).map_err(|diagnostics| {
+                diagnostics
+                    .iter()
+                    .map(|diagnostic| format!("{path}:{diagnostic}"))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })?;
+            let image = program.to_bytes();
+            self.program = Some(program);
+            self.source = Some(PathBuf::from(path));
+            image
+        } else {
+            std::fs::read(path).map_err(|e| format!("{path}: {e}"))?
+        };
+
+        let mut builder = LittleComputer3::builder()
+            .output(Console(self.sender.clone()))
+            .pause(self.pause.clone());
+        if arguments["os"].as_bool() == Some(true) {
+            builder = builder.os();
+        }
+        let mut machine = builder.build().map_err(|e| e.to_string())?;
+        machine
+            .load_program(image.as_slice())
+            .map_err(|e| e.to_string())?;
+        // only a launch that succeeded uses up the keyboard
+        if let Some(keyboard) = self.keyboard.take() {
+            machine.set_input(NonBlockingReader::new(keyboard));
+        }
+
+        self.machine = Some(machine);
+        self.stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or_default();
+        self.events.push(("initialized", Value::Null));
+        Ok(Value::Null)
+    }
+
+    /// Maps each requested line to the first instruction assembled from it
+    /// or, failing that, from a later line.
+    fn set_breakpoints(&mut self, arguments: &Value) -> Result<Value, String> {
+        self.breakpoints.clear();
+        let requested = arguments["breakpoints"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+
+        let path = arguments["source"]["path"].as_str().map(Path::new);
+        let program = match (&self.program, &self.source, path) {
+            (Some(program), Some(source), Some(path)) if same_file(source, path) => program,
+            _ => {
+                let unverified = json!({
+                    "verified": false,
+                    "message": "breakpoints need the program's .asm source",
+                });
+                return Ok(json!({ "breakpoints": vec![unverified; requested.len()] }));
+            }
+        };
+
+        let addresses = (0..program.words().len())
+            .map(|offset| program.origin().wrapping_add(offset as u16))
+            .filter_map(|address| Some((program.line(address)?, address)));
+        let mut code = BTreeMap::new();
+        for (line, address) in addresses {
+            code.entry(line).or_insert(address);
+        }
+
+        let breakpoints = requested
+            .iter()
+            .map(|breakpoint| {
+                let line = breakpoint["line"].as_u64().unwrap_or_default() as usize;
+                match code.range(line..).next() {
+                    Some((line, address)) => {
+                        self.breakpoints.insert(*address);
+                        json!({ "verified": true, "line": line })
+                    }
+                    None => {
+                        json!({ "verified": false, "message": "no code on or after this line" })
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    fn machine(&self) -> Result<&LittleComputer3, String> {
+        self.machine
+            .as_ref()
+            .ok_or_else(|| "no program was launched".to_string())
+    }
+
+    fn stack_trace(&self) -> Result<Value, String> {
+        let machine = self.machine()?;
+        let pc = machine.registers().get_pc();
+        let name = machine
+            .disassemble(pc..=pc)
+            .pop()
+            .map(|line| line.text)
+            .unwrap_or_default();
+
+        let mut frame = json!({
+            "id": 0,
+            "name": name,
+            "line": 0,
+            "column": 0,
+            "instructionPointerReference": format!("x{pc:04X}"),
+        });
+        let line = self.program.as_ref().and_then(|program| program.line(pc));
+        if let (Some(line), Some(source)) = (line, &self.source) {
+            frame["line"] = json!(line);
+            frame["column"] = json!(1);
+            frame["source"] = json!({
+                "name": source.file_name().map(|name| name.to_string_lossy()),
+                "path": source,
+            });
+        }
+        Ok(json!({ "stackFrames": [frame], "totalFrames": 1 }))
+    }
+
+    fn variables(&self, arguments: &Value) -> Result<Value, String> {
+        let machine = self.machine()?;
+        let registers = machine.registers();
+        let variables: Vec<_> = match arguments["variablesReference"].as_u64() {
+            Some(REGISTERS_REFERENCE) => {
+                let condition = registers.get(RegistersEnum::Condition);
+                let flags = [(0b100, 'N'), (0b010, 'Z'), (0b001, 'P')]
+                    .iter()
+                    .filter(|(bit, _)| condition & bit != 0)
+                    .map(|(_, flag)| *flag)
+                    .collect::<String>();
+                REGISTERS
+                    .iter()
+                    .map(|(name, register)| variable(name, registers.get(*register)))
+                    .chain(std::iter::once(
+                        json!({ "name": "CC", "value": flags, "variablesReference": 0 }),
+                    ))
+                    .collect()
+            }
+            Some(MEMORY_REFERENCE) => {
+                let memory = machine.memory();
+                match self.program.as_ref().map(Program::symbols) {
+                    Some(symbols) if !symbols.is_empty() => {
+                        let mut labels: Vec<_> = symbols.iter().collect();
+                        labels.sort_by_key(|(_, address)| **address);
+                        labels
+                            .into_iter()
+                            .map(|(label, address)| {
+                                let name = format!("{label} x{address:04X}");
+                                variable(&name, memory.peek(*address))
+                            })
+                            .collect()
+                    }
+                    _ => {
+                        let pc = registers.get_pc();
+                        (0..MEMORY_WINDOW)
+                            .map(|offset| pc.wrapping_add(offset))
+                            .map(|address| {
+                                variable(&format!("x{address:04X}"), memory.peek(address))
+                            })
+                            .collect()
+                    }
+                }
+            }
+            _ => return Err("unknown variables reference".to_string()),
+        };
+        Ok(json!({ "variables": variables }))
+    }
+
+    /// Console input was typed into the program already; other expressions
+    /// are a register, a label or an address whose word is shown.
+    fn evaluate(&self, arguments: &Value) -> Result<Value, String> {
+        if arguments["context"] == "repl" {
+            return Ok(json!({ "result": "", "variablesReference": 0 }));
+        }
+
+        let machine = self.machine()?;
+        let expression = arguments["expression"].as_str().unwrap_or_default().trim();
+        let register = REGISTERS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(expression));
+        let value = if let Some((_, register)) = register {
+            machine.registers().get(*register)
+        } else {
+            let address = self
+                .program
+                .as_ref()
+                .and_then(|program| program.symbols().get(expression).copied())
+                .or_else(|| asm::parse_number(expression).map(|number| number as u16))
+                .ok_or_else(|| format!("cannot evaluate '{expression}'"))?;
+            machine.memory().peek(address)
+        };
+        Ok(json!({ "result": format_word(value), "variablesReference": 0 }))
+    }
+
+    fn resume(&mut self, mode: Mode) -> Result<Value, String> {
+        self.machine()?;
+        self.mode = Some(mode);
+        Ok(json!({ "allThreadsContinued": true }))
+    }
+
+    fn stopped(&mut self, reason: &str) {
+        self.events.push((
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+        ));
+    }
+
+    fn flush_events(&mut self) -> std::io::Result<()> {
+        for (event, body) in std::mem::take(&mut self.events) {
+            self.sender.event(event, body)?;
+        }
+        Ok(())
+    }
+
+    /// Runs the program in the current mode until it stops or a request
+    /// arrives.
+    fn advance(&mut self) -> Result<(), Error> {
+        let (Some(machine), Some(mode)) = (self.machine.as_mut(), self.mode.as_mut()) else {
+            self.mode = None;
+            return Ok(());
+        };
+
+        let breakpoints = &self.breakpoints;
+        let mut reason = None;
+        let result = machine.run_until(|step| {
+            let done = match mode {
+                Mode::Continue => false,
+                Mode::StepIn => true,
+                Mode::Next { depth } => {
+                    *depth += frame_change(step);
+                    *depth <= 0
+                }
+                Mode::StepOut { depth } => {
+                    *depth += frame_change(step);
+                    *depth < 0
+                }
+            };
+            if done {
+                reason = Some("step");
+            } else if breakpoints.contains(&step.pc_after) {
+                reason = Some("breakpoint");
+            }
+            reason.is_some()
+        });
+
+        match result {
+            Ok(RunOutcome::Halted { .. }) => {
+                let exit_code = machine.registers().get(RegistersEnum::R0) & 0xFF;
+                self.mode = None;
+                self.sender
+                    .event("exited", json!({ "exitCode": exit_code }))?;
+                self.sender.event("terminated", Value::Null)?;
+            }
+            Ok(RunOutcome::Paused { .. }) => {
+                if let Some(reason) = reason {
+                    self.mode = None;
+                    self.stopped(reason);
+                    self.flush_events()?;
+                }
+            }
+            Err(Error::IoError(e)) => return Err(e.into()),
+            Err(error) => {
+                self.mode = None;
+                let output = format!("{error}\n");
+                self.sender
+                    .event("output", json!({ "category": "stderr", "output": output }))?;
+                self.sender.event("terminated", Value::Null)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn variable(name: &str, value: u16) -> Value {
+    json!({ "name": name, "value": format_word(value), "variablesReference": 0 })
+}
+
+fn format_word(value: u16) -> String {
+    format!("x{value:04X} ({})", value as i16)
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::VecDeque,
+        io::{BufReader, PipeReader, PipeWriter, Write},
+        path::PathBuf,
+        thread::{self, JoinHandle},
+    };
+
+    use serde_json::{json, Value};
+
+    use super::{read_message, serve};
+
+    const SOURCE: &str = "
+        .ORIG x3000
+START   LEA R0, HELLO
+        PUTS
+        AND R1, R1, #0
+        JSR SUB
+        ADD R1, R1, #1
+        HALT
+SUB     ADD R2, R2, #1
+        RET
+HELLO   .STRINGZ \"hi\"
+        .END
+";
+
+    /// A scripted client that keeps the events it is not waiting for yet.
+    struct Client {
+        requests: PipeWriter,
+        messages: BufReader<PipeReader>,
+        events: VecDeque<Value>,
+        seq: u64,
+        server: Option<JoinHandle<()>>,
+    }
+
+    impl Client {
+        fn start() -> Self {
+            let (requests_in, requests) = std::io::pipe().unwrap();
+            let (messages, messages_out) = std::io::pipe().unwrap();
+            // the machine's memory makes for large frames in debug builds
+            let server = thread::Builder::new()
+                .stack_size(16 << 20)
+                .spawn(move || serve(BufReader::new(requests_in), messages_out).unwrap())
+                .unwrap();
+            Self {
+                requests,
+                messages: BufReader::new(messages),
+                events: VecDeque::new(),
+                seq: 0,
+                server: Some(server),
+            }
+        }
+
+        fn request(&mut self, command: &str, arguments: Value) -> Value {
+            self.seq += 1;
+            let request = json!({
+                "seq": self.seq,
+                "type": "request",
+                "command": command,
+                "arguments": arguments,
+            })
+            .to_string();
+            write!(
+                self.requests,
+                "Content-Length: {}\r\n\r\n{request}",
+                request.len()
+            )
+            .unwrap();
+
+            loop {
+                let message = read_message(&mut self.messages).unwrap().unwrap();
+                if message["type"] == "response" && message["request_seq"] == self.seq {
+                    assert_eq!(command, message["command"]);
+                    return message;
+                }
+                self.events.push_back(message);
+            }
+        }
+
+        fn event(&mut self, event: &str) -> Value {
+            loop {
+                let message = match self.events.pop_front() {
+                    Some(message) => message,
+                    None => read_message(&mut self.messages).unwrap().unwrap(),
+                };
+                if message["event"] == event {
+                    return message["body"].clone();
+                }
+            }
+        }
+
+        fn line(&mut self) -> Value {
+            let trace = self.request("stackTrace", json!({ "threadId": 1 }));
+            trace["body"]["stackFrames"][0]["line"].clone()
+        }
+
+        fn disconnect(mut self) {
+            self.request("disconnect", json!({}));
+            self.server.take().unwrap().join().unwrap();
+        }
+    }
+
+    fn source_file(name: &str, source: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("lc3-dap-{}-{name}.asm", std::process::id()));
+        std::fs::write(&path, source).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_breakpoints_and_stepping() {
+        let path = source_file("stepping", SOURCE);
+        let mut client = Client::start();
+
+        let response = client.request("initialize", json!({ "adapterID": "lc3" }));
+        assert_eq!(true, response["body"]["supportsConfigurationDoneRequest"]);
+        let response = client.request("launch", json!({ "program": path, "stopOnEntry": true }));
+        assert_eq!(true, response["success"]);
+        client.event("initialized");
+
+        let response = client.request(
+            "setBreakpoints",
+            json!({ "source": { "path": path }, "breakpoints": [{ "line": 6 }, { "line": 13 }] }),
+        );
+        assert_eq!(
+            json!([{ "verified": true, "line": 6 }, { "verified": false, "message": "no code on or after this line" }]),
+            response["body"]["breakpoints"]
+        );
+        client.request("configurationDone", json!({}));
+        assert_eq!("entry", client.event("stopped")["reason"]);
+        assert_eq!(3, client.line());
+
+        client.request("continue", json!({ "threadId": 1 }));
+        // the program's output arrives as it is written, character by character
+        assert_eq!("h", client.event("output")["output"]);
+        assert_eq!("i", client.event("output")["output"]);
+        assert_eq!("breakpoint", client.event("stopped")["reason"]);
+        assert_eq!(6, client.line());
+
+        client.request("next", json!({ "threadId": 1 }));
+        assert_eq!("step", client.event("stopped")["reason"]);
+        assert_eq!(7, client.line());
+
+        let scopes = client.request("scopes", json!({ "frameId": 0 }));
+        let registers = scopes["body"]["scopes"][0]["variablesReference"].clone();
+        let variables = client.request("variables", json!({ "variablesReference": registers }));
+        let variables = variables["body"]["variables"].as_array().unwrap().clone();
+        assert!(variables
+            .contains(&json!({ "name": "R2", "value": "x0001 (1)", "variablesReference": 0 })));
+        assert!(variables.contains(&json!({ "name": "CC", "value": "P", "variablesReference": 0 })));
+
+        let hover = client.request(
+            "evaluate",
+            json!({ "expression": "HELLO", "context": "hover" }),
+        );
+        assert_eq!("x0068 (104)", hover["body"]["result"]);
+
+        client.request("stepIn", json!({ "threadId": 1 }));
+        assert_eq!("step", client.event("stopped")["reason"]);
+        assert_eq!(8, client.line());
+
+        client.request("continue", json!({ "threadId": 1 }));
+        client.event("exited");
+        client.event("terminated");
+        client.disconnect();
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_console_input() {
+        let path = source_file("console", ".ORIG x3000\nGETC\nOUT\nHALT\n.END\n");
+        let mut client = Client::start();
+
+        client.request("initialize", json!({ "adapterID": "lc3" }));
+        client.request("launch", json!({ "program": path }));
+        client.request("configurationDone", json!({}));
+        client.request("evaluate", json!({ "expression": "a", "context": "repl" }));
+        assert_eq!("a", client.event("output")["output"]);
+        assert_eq!(json!({ "exitCode": 0x61 }), client.event("exited"));
+        client.disconnect();
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_pause_while_waiting_for_a_key() {
+        let path = source_file("pause", ".ORIG x3000\nGETC\nOUT\nHALT\n.END\n");
+        let mut client = Client::start();
+
+        client.request("initialize", json!({ "adapterID": "lc3" }));
+        client.request("launch", json!({ "program": path }));
+        client.request("configurationDone", json!({}));
+        client.request("pause", json!({ "threadId": 1 }));
+        assert_eq!("pause", client.event("stopped")["reason"]);
+        // GETC runs again once the program continues
+        assert_eq!(2, client.line());
+
+        client.request("continue", json!({ "threadId": 1 }));
+        client.request("evaluate", json!({ "expression": "a", "context": "repl" }));
+        assert_eq!("a", client.event("output")["output"]);
+        assert_eq!(json!({ "exitCode": 0x61 }), client.event("exited"));
+        client.disconnect();
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut client = Client::start();
+        let response = client.request("stackTrace", json!({ "threadId": 1 }));
+        assert_eq!(false, response["success"]);
+        assert_eq!("no program was launched", response["message"]);
+
+        let response = client.request("launch", json!({}));
+        assert_eq!(false, response["success"]);
+        let response = client.request("bogus", json!({}));
+        assert_eq!("unsupported request 'bogus'", response["message"]);
+
+        // a failed launch can be retried
+        let path = source_file("retry", ".ORIG x3000\nGETC\nOUT\nHALT\n.END\n");
+        let response = client.request("launch", json!({ "program": path }));
+        assert_eq!(true, response["success"]);
+        client.event("initialized");
+        let response = client.request("launch", json!({ "program": path }));
+        assert_eq!("a program was launched already", response["message"]);
+        client.request("configurationDone", json!({}));
+        client.request("evaluate", json!({ "expression": "b", "context": "repl" }));
+        assert_eq!("b", client.event("output")["output"]);
+        client.disconnect();
+        std::fs::remove_file(path).unwrap();
+    }
+}
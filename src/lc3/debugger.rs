@@ -0,0 +1,897 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{BufRead, Write},
+    ops::RangeInclusive,
+};
+
+use crate::vm::{memory::MemoryTrait, registers::RegistersTrait};
+
+use super::{
+    asm::parse_number,
+    disasm::{self, Line},
+    error::Error,
+    history::Undo,
+    instructions::Instructions,
+    machine::{LittleComputer3, RunOutcome, StepOutcome},
+    memory::{Access, AccessKind},
+    registers::RegistersEnum,
+};
+
+const HELP: &str = "\
+break <location>           stop when execution reaches location (b)
+delete [location]          remove one or all breakpoints (d)
+breakpoints                list the breakpoints
+step [count]               execute instructions, entering subroutines and traps (s)
+next [count]               like step, but run subroutines, traps and interrupts to completion (n)
+continue                   run until a breakpoint or halt (c)
+finish                     run until the current subroutine or trap returns
+reverse-step [count]       undo the last instructions (rs)
+reverse-continue           run backwards to a breakpoint or a watched write (rc)
+lastwrite <location>       show the last recorded instruction that wrote location
+history                    show how many instructions can be undone
+watch <range>              stop after memory in range is written (w)
+rwatch <range>             stop after memory in range is read
+awatch <range>             stop after memory in range is read or written
+unwatch [location]         remove the watchpoints covering location, or all
+watchpoints                list the watchpoints
+registers                  show the registers (r)
+set <register|location> <value|location>
+                           change a register or a memory word
+x/<count> <location>       examine count words of memory
+disassemble [location [count]]
+                           disassemble around the PC or from location (disas)
+quit                       leave the debugger (q)
+
+Ctrl-C interrupts a running program.
+Locations are numbers (x3000, 0x3000, #12288, 12288), labels or registers.
+Ranges are a single location or <location>..<location>.
+An empty line repeats the previous command.";
+
+enum Control {
+    Continue,
+    Quit,
+}
+
+enum CommandError {
+    Usage(String),
+    Machine(Error),
+}
+
+impl From<Error> for CommandError {
+    fn from(error: Error) -> Self {
+        CommandError::Machine(error)
+    }
+}
+
+impl From<std::io::Error> for CommandError {
+    fn from(error: std::io::Error) -> Self {
+        CommandError::Machine(error.into())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    /// Both reads and writes.
+    Access,
+}
+
+/// Pauses execution after an instruction accessed memory in `range`.
+/// Instruction fetches do not count as reads.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub range: RangeInclusive<u16>,
+    pub kind: WatchKind,
+}
+
+impl Watchpoint {
+    pub fn matches(&self, access: &Access) -> bool {
+        let kind = match (self.kind, access.kind) {
+            (_, AccessKind::Fetch) => false,
+            (WatchKind::Access, _) => true,
+            (WatchKind::Read, kind) => kind == AccessKind::Read,
+            (WatchKind::Write, kind) => kind == AccessKind::Write,
+        };
+        kind && self.range.contains(&access.address)
+    }
+}
+
+/// Interactive debugger that drives a [`LittleComputer3`] with gdb style
+/// text commands.
+#[derive(Debug, Default)]
+pub struct Debugger {
+    breakpoints: BTreeSet<u16>,
+    watchpoints: Vec<Watchpoint>,
+    symbols: BTreeMap<String, u16>,
+    last_command: String,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lets commands refer to labels and shows them in addresses.
+    pub fn with_symbols(symbols: BTreeMap<String, u16>) -> Self {
+        Self {
+            symbols,
+            ..Default::default()
+        }
+    }
+
+    pub fn breakpoints(&self) -> &BTreeSet<u16> {
+        &self.breakpoints
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// Reads commands from `commands` until `quit` or the end of the input.
+    pub fn run<C, O>(
+        &mut self,
+        machine: &mut LittleComputer3,
+        mut commands: C,
+        mut output: O,
+    ) -> Result<(), Error>
+    where
+        C: BufRead,
+        O: Write,
+    {
+        writeln!(output, "{}", self.location(machine))?;
+        loop {
+            write!(output, "(lc3) ")?;
+            output.flush()?;
+
+            let mut line = String::new();
+            if commands.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            let line = line.trim();
+            if !line.is_empty() {
+                self.last_command = line.to_string();
+            }
+
+            let command = self.last_command.clone();
+            let words: Vec<&str> = command.split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+            match self.execute(machine, &words, &mut output) {
+                Ok(Control::Continue) => {}
+                Ok(Control::Quit) => return Ok(()),
+                Err(CommandError::Usage(message)) => writeln!(output, "{message}")?,
+                Err(CommandError::Machine(Error::IoError(error))) => return Err(error.into()),
+                Err(CommandError::Machine(error)) => writeln!(output, "error: {error}")?,
+            }
+        }
+    }
+
+    fn execute(
+        &mut self,
+        machine: &mut LittleComputer3,
+        words: &[&str],
+        output: &mut dyn Write,
+    ) -> Result<Control, CommandError> {
+        match words {
+            ["b" | "break", location] => {
+                let address = self.address(machine, location)?;
+                self.breakpoints.insert(address);
+                writeln!(output, "breakpoint at {}", self.describe(address))?;
+            }
+            ["d" | "delete"] => self.breakpoints.clear(),
+            ["d" | "delete", location] => {
+                let address = self.address(machine, location)?;
+                if !self.breakpoints.remove(&address) {
+                    return Err(CommandError::Usage(format!(
+                        "no breakpoint at {}",
+                        self.describe(address)
+                    )));
+                }
+            }
+            ["breakpoints"] => {
+                for address in &self.breakpoints {
+                    writeln!(output, "{}", self.describe(*address))?;
+                }
+            }
+            ["w" | "watch", range] => self.watch(machine, range, WatchKind::Write, output)?,
+            ["rwatch", range] => self.watch(machine, range, WatchKind::Read, output)?,
+            ["awatch", range] => self.watch(machine, range, WatchKind::Access, output)?,
+            ["unwatch"] => self.watchpoints.clear(),
+            ["unwatch", location] => {
+                let address = self.address(machine, location)?;
+                let count = self.watchpoints.len();
+                self.watchpoints
+                    .retain(|watchpoint| !watchpoint.range.contains(&address));
+                if self.watchpoints.len() == count {
+                    return Err(CommandError::Usage(format!(
+                        "no watchpoint at {}",
+                        self.describe(address)
+                    )));
+                }
+            }
+            ["watchpoints"] => {
+                for watchpoint in &self.watchpoints {
+                    writeln!(output, "{}", self.describe_watchpoint(watchpoint))?;
+                }
+            }
+            ["s" | "step", count @ ..] => {
+                for _ in 0..self.count(count, 1)? {
+                    if self.resume(machine, output, |_| true)? {
+                        break;
+                    }
+                }
+                writeln!(output, "{}", self.location(machine))?;
+            }
+            ["n" | "next", count @ ..] => {
+                for _ in 0..self.count(count, 1)? {
+                    let mut depth = 0;
+                    let triggered = self.resume(machine, output, |step| {
+                        depth += frame_change(step);
+                        depth <= 0
+                    })?;
+                    if triggered {
+                        break;
+                    }
+                }
+                writeln!(output, "{}", self.location(machine))?;
+            }
+            ["c" | "continue"] => {
+                self.resume(machine, output, |_| false)?;
+                writeln!(output, "{}", self.location(machine))?;
+            }
+            ["finish"] => {
+                let mut depth = 0;
+                self.resume(machine, output, |step| {
+                    depth += frame_change(step);
+                    depth < 0
+                })?;
+                writeln!(output, "{}", self.location(machine))?;
+            }
+            ["rs" | "reverse-step", count @ ..] => {
+                for _ in 0..self.count(count, 1)? {
+                    if machine.step_back().is_none() {
+                        writeln!(output, "no more history")?;
+                        break;
+                    }
+                }
+                writeln!(output, "{}", self.location(machine))?;
+            }
+            ["rc" | "reverse-continue"] => {
+                self.reverse(machine, output)?;
+                writeln!(output, "{}", self.location(machine))?;
+            }
+            ["lastwrite", location] => {
+                let address = self.address(machine, location)?;
+                match machine.history().last_write(address) {
+                    Some((undo, write)) => {
+                        writeln!(
+                            output,
+                            "{} last written at cycle {}: x{:04X} -> x{:04X}",
+                            self.describe(address),
+                            undo.cycle(),
+                            write.old,
+                            write.value
+                        )?;
+                        self.print_writer(undo, output)?;
+                    }
+                    None => writeln!(
+                        output,
+                        "no write to {} in the history",
+                        self.describe(address)
+                    )?,
+                }
+            }
+            ["history"] => {
+                let history = machine.history();
+                writeln!(
+                    output,
+                    "{} of {} instructions recorded",
+                    history.len(),
+                    history.capacity()
+                )?;
+            }
+            ["r" | "registers"] => self.print_registers(machine, output)?,
+            ["set", target, value] => {
+                let value = self.address(machine, value)?;
+                match register(target) {
+                    Some(register) => machine.registers_mut().set(register, value),
+                    None => {
+                        let address = self.address(machine, target)?;
+                        machine.memory_mut().write(address, value);
+                    }
+                }
+            }
+            [examine, location @ ..] if *examine == "x" || examine.starts_with("x/") => {
+                let count = match examine.strip_prefix("x/") {
+                    Some(count) => number(count)?,
+                    None => 1,
+                };
+                let start = match location {
+                    [] => machine.registers().get_pc(),
+                    [location] => self.address(machine, location)?,
+                    _ => return Err(usage("x/<count> <location>")),
+                };
+                self.print_memory(machine, start, count, output)?;
+            }
+            ["disas" | "disassemble", rest @ ..] => {
+                let pc = machine.registers().get_pc();
+                let (start, count) = match rest {
+                    [] => (pc.wrapping_sub(4), 10),
+                    [location] => (self.address(machine, location)?, 10),
+                    [location, count] => (self.address(machine, location)?, number(count)?),
+                    _ => return Err(usage("disassemble [location [count]]")),
+                };
+                self.print_disassembly(machine, start, count, output)?;
+            }
+            ["h" | "help"] => writeln!(output, "{HELP}")?,
+            ["q" | "quit"] => return Ok(Control::Quit),
+            [command, ..] => {
+                return Err(CommandError::Usage(format!(
+                    "unknown command '{command}', try 'help'"
+                )))
+            }
+            [] => {}
+        }
+        Ok(Control::Continue)
+    }
+
+    /// Runs until `stop` returns `true`, a breakpoint or watchpoint is
+    /// reached, the machine halts or the run is interrupted by pausing the
+    /// machine, e.g. with Ctrl-C. Returns whether a watchpoint triggered or
+    /// the run was interrupted, after reporting it.
+    fn resume<S>(
+        &self,
+        machine: &mut LittleComputer3,
+        output: &mut dyn Write,
+        mut stop: S,
+    ) -> Result<bool, Error>
+    where
+        S: FnMut(&StepOutcome) -> bool,
+    {
+        if machine.is_halted() {
+            return Err(Error::MachineHalted);
+        }
+
+        // forget a pause requested while the debugger waited for a command
+        if let Some(pause) = machine.pause() {
+            pause.take();
+        }
+        let mut hit = None;
+        let mut stopped = false;
+        machine.set_record_accesses(!self.watchpoints.is_empty());
+        let outcome = machine.run_until(|step| {
+            hit = step
+                .accesses
+                .iter()
+                .find(|access| {
+                    self.watchpoints
+                        .iter()
+                        .any(|watchpoint| watchpoint.matches(access))
+                })
+                .map(|access| {
+                    (
+                        *access,
+                        step.pc_before,
+                        step.instruction.at(step.pc_before).to_string(),
+                    )
+                });
+            stopped = hit.is_some() || stop(step) || self.breakpoints.contains(&step.pc_after);
+            stopped
+        })?;
+
+        let Some((access, pc, instruction)) = hit else {
+            let interrupted = !stopped && matches!(outcome, RunOutcome::Paused { .. });
+            if interrupted {
+                writeln!(output, "interrupted")?;
+            }
+            return Ok(interrupted);
+        };
+        let location = self.describe(access.address);
+        match access.kind {
+            AccessKind::Write => writeln!(
+                output,
+                "watchpoint {location} written: x{:04X} -> x{:04X}",
+                access.old, access.value
+            )?,
+            _ => writeln!(output, "watchpoint {location} read: x{:04X}", access.value)?,
+        }
+        writeln!(output, "  by {}: {instruction}", self.describe(pc))?;
+        Ok(true)
+    }
+
+    /// Steps back until the PC reaches a breakpoint, an undone instruction
+    /// wrote watched memory or the history is exhausted.
+    fn reverse(&self, machine: &mut LittleComputer3, output: &mut dyn Write) -> Result<(), Error> {
+        loop {
+            let Some(undo) = machine.step_back() else {
+                writeln!(output, "no more history")?;
+                return Ok(());
+            };
+            let hit = undo.writes().iter().find(|write| {
+                self.watchpoints
+                    .iter()
+                    .any(|watchpoint| watchpoint.matches(write))
+            });
+            if let Some(write) = hit {
+                writeln!(
+                    output,
+                    "watchpoint {} written: x{:04X} -> x{:04X}",
+                    self.describe(write.address),
+                    write.old,
+                    write.value
+                )?;
+                return self.print_writer(&undo, output);
+            }
+            if self.breakpoints.contains(&machine.registers().get_pc()) {
+                return Ok(());
+            }
+        }
+    }
+
+    fn print_writer(&self, undo: &Undo, output: &mut dyn Write) -> Result<(), Error> {
+        let instruction = disasm::disassemble(undo.pc(), undo.word());
+        writeln!(output, "  by {}: {instruction}", self.describe(undo.pc()))?;
+        Ok(())
+    }
+
+    fn watch(
+        &mut self,
+        machine: &LittleComputer3,
+        range: &str,
+        kind: WatchKind,
+        output: &mut dyn Write,
+    ) -> Result<(), CommandError> {
+        let range = match range.split_once("..") {
+            Some((start, end)) => self.address(machine, start)?..=self.address(machine, end)?,
+            None => {
+                let address = self.address(machine, range)?;
+                address..=address
+            }
+        };
+        if range.is_empty() {
+            return Err(usage("watch <location>[..<location>]"));
+        }
+
+        let watchpoint = Watchpoint { range, kind };
+        writeln!(output, "{}", self.describe_watchpoint(&watchpoint))?;
+        self.watchpoints.push(watchpoint);
+        Ok(())
+    }
+
+    fn describe_watchpoint(&self, watchpoint: &Watchpoint) -> String {
+        let kind = match watchpoint.kind {
+            WatchKind::Read => "read",
+            WatchKind::Write => "write",
+            WatchKind::Access => "access",
+        };
+        let (start, end) = (*watchpoint.range.start(), *watchpoint.range.end());
+        if start == end {
+            format!("{kind} watchpoint at {}", self.describe(start))
+        } else {
+            format!(
+                "{kind} watchpoint at {} to {}",
+                self.describe(start),
+                self.describe(end)
+            )
+        }
+    }
+
+    fn count(&self, words: &[&str], default: u16) -> Result<u16, CommandError> {
+        match words {
+            [] => Ok(default),
+            [count] => number(count),
+            _ => Err(usage("expected a single count")),
+        }
+    }
+
+    fn address(&self, machine: &LittleComputer3, location: &str) -> Result<u16, CommandError> {
+        if let Ok(address) = number(location) {
+            return Ok(address);
+        }
+        if let Some(address) = self.symbols.get(location) {
+            return Ok(*address);
+        }
+        match register(location) {
+            Some(register) => Ok(machine.registers().get(register)),
+            None => Err(CommandError::Usage(format!(
+                "'{location}' is neither an address, a label nor a register"
+            ))),
+        }
+    }
+
+    /// Formats an address together with the closest label before it.
+    fn describe(&self, address: u16) -> String {
+        let label = self
+            .symbols
+            .iter()
+            .filter(|(_, symbol)| **symbol <= address)
+            .max_by_key(|(_, symbol)| **symbol);
+        match label {
+            Some((name, symbol)) if *symbol == address => format!("x{address:04X} <{name}>"),
+            Some((name, symbol)) => format!("x{address:04X} <{name}+{}>", address - symbol),
+            None => format!("x{address:04X}"),
+        }
+    }
+
+    fn location(&self, machine: &LittleComputer3) -> String {
+        let pc = machine.registers().get_pc();
+        let state = if machine.is_halted() {
+            "halted at "
+        } else if self.breakpoints.contains(&pc) {
+            "breakpoint "
+        } else {
+            ""
+        };
+        let instruction = disasm::disassemble(pc, machine.memory().peek(pc));
+        format!("{state}{}: {instruction}", self.describe(pc))
+    }
+
+    fn print_registers(
+        &self,
+        machine: &LittleComputer3,
+        output: &mut dyn Write,
+    ) -> Result<(), Error> {
+        let registers = machine.registers();
+        for row in [0u16, 4] {
+            let line: Vec<String> = (row..row + 4)
+                .map(|index| {
+                    let register = index.try_into().expect("R0 to R7");
+                    format!("R{index} x{:04X}", registers.get(register))
+                })
+                .collect();
+            writeln!(output, "{}", line.join("  "))?;
+        }
+
+        let condition = registers.get(RegistersEnum::Condition);
+        let flags: String = [(0x4, 'N'), (0x2, 'Z'), (0x1, 'P')]
+            .iter()
+            .filter(|(flag, _)| condition & flag != 0)
+            .map(|(_, name)| name)
+            .collect();
+        writeln!(
+            output,
+            "PC x{:04X}  PSR x{:04X}  CC {}",
+            registers.get_pc(),
+            registers.get(RegistersEnum::ProcessorStatus),
+            if flags.is_empty() { "-" } else { &flags }
+        )?;
+        Ok(())
+    }
+
+    fn print_memory(
+        &self,
+        machine: &LittleComputer3,
+        start: u16,
+        count: u16,
+        output: &mut dyn Write,
+    ) -> Result<(), Error> {
+        for row in (0..count).step_by(8) {
+            let address = start.wrapping_add(row);
+            let words: Vec<String> = (row..count.min(row.saturating_add(8)))
+                .map(|offset| format!("x{:04X}", machine.memory().peek(start.wrapping_add(offset))))
+                .collect();
+            writeln!(output, "{}: {}", self.describe(address), words.join(" "))?;
+        }
+        Ok(())
+    }
+
+    fn print_disassembly(
+        &self,
+        machine: &LittleComputer3,
+        start: u16,
+        count: u16,
+        output: &mut dyn Write,
+    ) -> Result<(), Error> {
+        let pc = machine.registers().get_pc();
+        for offset in 0..count {
+            let address = start.wrapping_add(offset);
+            for (name, _) in self
+                .symbols
+                .iter()
+                .filter(|(_, symbol)| **symbol == address)
+            {
+                writeln!(output, "{name}:")?;
+            }
+
+            let word = machine.memory().peek(address);
+            let line = Line {
+                address,
+                word,
+                text: disasm::disassemble(address, word),
+            };
+            let current = if address == pc { "=>" } else { "  " };
+            let breakpoint = if self.breakpoints.contains(&address) {
+                '*'
+            } else {
+                ' '
+            };
+            writeln!(output, "{current}{breakpoint} {line}")?;
+        }
+        Ok(())
+    }
+}
+
+fn usage(message: &str) -> CommandError {
+    CommandError::Usage(format!("usage: {message}"))
+}
+
+fn number(text: &str) -> Result<u16, CommandError> {
+    parse_number(text)
+        .filter(|value| (i16::MIN as i32..=u16::MAX as i32).contains(value))
+        .map(|value| value as u16)
+        .ok_or_else(|| CommandError::Usage(format!("'{text}' is not a number")))
+}
+
+fn register(name: &str) -> Option<RegistersEnum> {
+    match name.to_ascii_uppercase().as_str() {
+        "PC" => Some(RegistersEnum::ProgramCounter),
+        "PSR" => Some(RegistersEnum::ProcessorStatus),
+        name => match name.as_bytes() {
+            [b'R', digit @ b'0'..=b'7'] => ((digit - b'0') as u16).try_into().ok(),
+            _ => None,
+        },
+    }
+}
+
+/// How a step changes the depth of nested subroutines, traps and interrupts.
+pub(crate) fn frame_change(step: &StepOutcome) -> i32 {
+    let change = match step.instruction {
+        Instructions::JumpRegister(_) | Instructions::RES => 1,
+        Instructions::Trap(_) if step.pc_after != step.pc_before.wrapping_add(1) => 1,
+        Instructions::Jump {
+            source: RegistersEnum::R7,
+        }
+        | Instructions::RTI => -1,
+        _ => 0,
+    };
+    change + step.interrupt.is_some() as i32
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::Cursor,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use crate::{
+        lc3::{
+            asm::assemble,
+            console::{Hotkey, Shared},
+            machine::LittleComputer3,
+            memory::{Access, AccessKind},
+            registers::RegistersEnum,
+            replay::Recording,
+        },
+        vm::registers::RegistersTrait,
+    };
+
+    use super::{Debugger, WatchKind, Watchpoint};
+
+    const SOURCE: &str = "
+        .ORIG x3000
+START   AND R0, R0, #0
+LOOP    ADD R0, R0, #1
+        JSR SUB
+        ADD R1, R0, #-3
+        BRn LOOP
+        HALT
+SUB     ADD R2, R2, #1
+        RET
+DATA    .FILL x1234
+        .FILL x5678
+";
+
+    fn debug(source: &str, commands: &str) -> (LittleComputer3, String) {
+        let program = assemble(source).unwrap();
+        let mut lc3 = LittleComputer3::builder()
+            .input(Cursor::new([]))
+            .output(Vec::new())
+            .history(1000)
+            .build()
+            .unwrap();
+        lc3.load_program(Cursor::new(program.to_bytes())).unwrap();
+
+        let output = Shared::new(Vec::new());
+        let mut debugger = Debugger::with_symbols(program.symbols().clone());
+        debugger
+            .run(&mut lc3, Cursor::new(commands), output.clone())
+            .unwrap();
+        let output = String::from_utf8(output.borrow().clone()).unwrap();
+        (lc3, output)
+    }
+
+    fn assert_in_order(output: &str, expected: &[&str]) {
+        let mut rest = output;
+        for line in expected {
+            let index = rest
+                .find(line)
+                .unwrap_or_else(|| panic!("'{line}' missing from:\n{output}"));
+            rest = &rest[index + line.len()..];
+        }
+    }
+
+    #[test]
+    fn test_breakpoints_and_stepping() {
+        let (lc3, output) = debug(
+            SOURCE,
+            "break LOOP\ncontinue\nstep\nnext\n\ncontinue\nstep 2\nfinish\nbreakpoints\ndelete LOOP\ncontinue\n",
+        );
+        assert_in_order(
+            &output,
+            &[
+                "x3000 <START>: AND R0, R0, #0",
+                "breakpoint at x3001 <LOOP>",
+                "breakpoint x3001 <LOOP>: ADD R0, R0, #1",
+                "x3002 <LOOP+1>: JSR x3006",
+                "x3003 <LOOP+2>: ADD R1, R0, #-3",
+                "x3004 <LOOP+3>: BRn x3001",
+                "breakpoint x3001 <LOOP>: ADD R0, R0, #1",
+                "x3006 <SUB>: ADD R2, R2, #1",
+                "x3003 <LOOP+2>: ADD R1, R0, #-3",
+                "x3001 <LOOP>\n",
+                "halted at x3006 <SUB>: ADD R2, R2, #1",
+            ],
+        );
+        assert_eq!(3, lc3.registers().get(RegistersEnum::R0));
+        assert_eq!(3, lc3.registers().get(RegistersEnum::R2));
+    }
+
+    #[test]
+    fn test_registers_and_memory() {
+        let (lc3, output) = debug(
+            SOURCE,
+            "set R3 x42\nset PC LOOP\nregisters\nx/2 DATA\nset DATA #-1\nx DATA\ndisassemble START 2\nbogus\nquit\nstep\n",
+        );
+        assert_in_order(
+            &output,
+            &[
+                "R0 x0000  R1 x0000  R2 x0000  R3 x0042",
+                "PC x3001  PSR x8000  CC -",
+                "x3008 <DATA>: x1234 x5678",
+                "x3008 <DATA>: xFFFF",
+                "START:\n    x3000  x5020  AND R0, R0, #0\nLOOP:\n=>  x3001  x1021  ADD R0, R0, #1",
+                "unknown command 'bogus', try 'help'",
+            ],
+        );
+        assert_eq!(0x3001, lc3.registers().get_pc());
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let source = "
+        .ORIG x3000
+        LD R0, COUNT
+        ADD R0, R0, #1
+        ST R0, COUNT
+        LEA R0, MSG
+        PUTS
+        HALT
+COUNT   .FILL #41
+MSG     .STRINGZ \"ok\"
+";
+        let (_, output) = debug(
+            source,
+            "watch COUNT\nrwatch MSG..x3009\nwatchpoints\ncontinue\ncontinue\nunwatch MSG\ncontinue\n",
+        );
+        assert_in_order(
+            &output,
+            &[
+                "write watchpoint at x3006 <COUNT>",
+                "read watchpoint at x3007 <MSG> to x3009 <MSG+2>",
+                "write watchpoint at x3006 <COUNT>\nread watchpoint at x3007 <MSG> to x3009 <MSG+2>",
+                "watchpoint x3006 <COUNT> written: x0029 -> x002A\n  by x3002: ST R0, x3006",
+                "x3003: LEA R0, x3007",
+                "watchpoint x3007 <MSG> read: x006F\n  by x3004: TRAP x22 ; PUTS",
+                "halted at",
+            ],
+        );
+    }
+
+    #[test]
+    fn test_reverse() {
+        let source = "
+        .ORIG x3000
+        AND R0, R0, #0
+LOOP    ADD R0, R0, #1
+        ST R0, DATA
+        ADD R1, R0, #-3
+        BRn LOOP
+        HALT
+DATA    .FILL x1234
+";
+        let (lc3, output) = debug(
+            source,
+            "b x3002\ncontinue\nd\ncontinue\nhistory\nrs\nr\nb x3002\nrc\nr\nd\nwatch DATA\nrc\nlastwrite DATA\nrs 100\nx DATA\n",
+        );
+        assert_in_order(
+            &output,
+            &[
+                "halted at",
+                "14 of 1000 instructions recorded",
+                "x3005 <LOOP+4>: TRAP x25 ; HALT",
+                "breakpoint x3002 <LOOP+1>: ST R0, x3006",
+                "R0 x0003  R1 xFFFF",
+                "watchpoint x3006 <DATA> written: x0001 -> x0002\n  by x3002 <LOOP+1>: ST R0, x3006",
+                "x3006 <DATA> last written at cycle 3: x1234 -> x0001\n  by x3002 <LOOP+1>: ST R0, x3006",
+                "no more history\nx3000: AND R0, R0, #0",
+                "x3006 <DATA>: x1234",
+            ],
+        );
+        assert!(!lc3.is_halted());
+        assert_eq!(0, lc3.cycles());
+    }
+
+    #[test]
+    fn test_interrupt() {
+        let program = assemble(".ORIG x3000\nSTART GETC\nHALT\n.END").unwrap();
+        let pause = Hotkey::default();
+        let mut lc3 = LittleComputer3::builder()
+            // no key is ever pressed
+            .replay_input(Recording::default())
+            .output(Vec::new())
+            .pause(pause.clone())
+            .build()
+            .unwrap();
+        lc3.load_program(Cursor::new(program.to_bytes())).unwrap();
+
+        // keep pressing Ctrl-C until the debugger is done
+        let done = Arc::new(AtomicBool::new(false));
+        let presser = {
+            let done = done.clone();
+            std::thread::spawn(move || {
+                while !done.load(Ordering::Relaxed) {
+                    std::thread::sleep(Duration::from_millis(5));
+                    pause.press();
+                }
+            })
+        };
+        let output = Shared::new(Vec::new());
+        let mut debugger = Debugger::with_symbols(program.symbols().clone());
+        debugger
+            .run(&mut lc3, Cursor::new("continue\nstep\n"), output.clone())
+            .unwrap();
+        done.store(true, Ordering::Relaxed);
+        presser.join().unwrap();
+
+        let output = String::from_utf8(output.borrow().clone()).unwrap();
+        assert_in_order(
+            &output,
+            &[
+                "interrupted\nx3000 <START>: TRAP x20 ; GETC",
+                "interrupted\nx3000 <START>: TRAP x20 ; GETC",
+            ],
+        );
+        assert_eq!(0, lc3.cycles());
+    }
+
+    #[test]
+    fn test_watchpoint_matches() {
+        let watchpoint = Watchpoint {
+            range: 0x4000..=0x4001,
+            kind: WatchKind::Access,
+        };
+        let access = |kind, address| Access {
+            kind,
+            address,
+            old: 0,
+            value: 0,
+        };
+        assert!(watchpoint.matches(&access(AccessKind::Read, 0x4000)));
+        assert!(watchpoint.matches(&access(AccessKind::Write, 0x4001)));
+        assert!(!watchpoint.matches(&access(AccessKind::Fetch, 0x4000)));
+        assert!(!watchpoint.matches(&access(AccessKind::Write, 0x4002)));
+
+        let watchpoint = Watchpoint {
+            kind: WatchKind::Write,
+            ..watchpoint
+        };
+        assert!(!watchpoint.matches(&access(AccessKind::Read, 0x4000)));
+    }
+}
@@ -0,0 +1,209 @@
+use std::{io::Read, ops::RangeInclusive};
+
+use super::{
+    display::Display,
+    error::Error,
+    interrupt::{Interrupt, InterruptController},
+    keyboard::Keyboard,
+    machine_control::MachineControl,
+    memory::MemoryMappedReg,
+};
+
+/// A memory mapped peripheral. Reads and writes to the addresses a device is
+/// attached to are sent to it instead of to memory.
+pub trait Device {
+    fn read(&mut self, address: u16, input: &mut dyn Read) -> u16;
+    fn write(&mut self, address: u16, value: u16);
+
+    /// The value a read would return, without the side effects of reading.
+    /// Returns 0 unless overridden; devices whose reads have no side effects
+    /// should return what [`Device::read`] would.
+    fn peek(&self, _address: u16) -> u16 {
+        0
+    }
+
+    /// Called once between instructions.
+    fn tick(&mut self, _input: &mut dyn Read) {}
+
+    /// The interrupt the device currently requests, if any.
+    fn interrupt(&self) -> Option<Interrupt> {
+        None
+    }
+
+    /// The device's internal state for a machine snapshot.
+    fn save(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Restores a state returned by [`Device::save`].
+    fn load(&mut self, _state: &[u8]) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Routes memory accesses to the built-in keyboard, display and machine
+/// control register and to any attached devices.
+#[derive(Default)]
+pub struct Bus {
+    keyboard: Keyboard,
+    display: Display,
+    machine_control: MachineControl,
+    devices: Vec<(RangeInclusive<u16>, Box<dyn Device>)>,
+}
+
+impl Bus {
+    pub fn keyboard(&self) -> &Keyboard {
+        &self.keyboard
+    }
+
+    pub fn keyboard_mut(&mut self) -> &mut Keyboard {
+        &mut self.keyboard
+    }
+
+    pub fn display_mut(&mut self) -> &mut Display {
+        &mut self.display
+    }
+
+    pub fn machine_control(&self) -> &MachineControl {
+        &self.machine_control
+    }
+
+    pub fn machine_control_mut(&mut self) -> &mut MachineControl {
+        &mut self.machine_control
+    }
+
+    /// Attaches `device` to `range`. Later devices take precedence where
+    /// ranges overlap, and all of them over the built-in devices.
+    pub fn attach(&mut self, range: RangeInclusive<u16>, device: Box<dyn Device>) {
+        self.devices.push((range, device));
+    }
+
+    pub fn device(&self, address: u16) -> Option<&dyn Device> {
+        if let Some((_, device)) = self
+            .devices
+            .iter()
+            .rev()
+            .find(|(range, _)| range.contains(&address))
+        {
+            return Some(device.as_ref());
+        }
+
+        match address {
+            a if a == MemoryMappedReg::Kbsr as u16 || a == MemoryMappedReg::Kbdr as u16 => {
+                Some(&self.keyboard)
+            }
+            a if a == MemoryMappedReg::Dsr as u16 || a == MemoryMappedReg::Ddr as u16 => {
+                Some(&self.display)
+            }
+            a if a == MemoryMappedReg::Mcr as u16 => Some(&self.machine_control),
+            _ => None,
+        }
+    }
+
+    pub fn device_mut(&mut self, address: u16) -> Option<&mut dyn Device> {
+        if let Some(index) = self
+            .devices
+            .iter()
+            .rposition(|(range, _)| range.contains(&address))
+        {
+            return Some(self.devices[index].1.as_mut());
+        }
+
+        match address {
+            a if a == MemoryMappedReg::Kbsr as u16 || a == MemoryMappedReg::Kbdr as u16 => {
+                Some(&mut self.keyboard)
+            }
+            a if a == MemoryMappedReg::Dsr as u16 || a == MemoryMappedReg::Ddr as u16 => {
+                Some(&mut self.display)
+            }
+            a if a == MemoryMappedReg::Mcr as u16 => Some(&mut self.machine_control),
+            _ => None,
+        }
+    }
+
+    /// The built-in devices followed by the attached ones, in the order
+    /// they were attached.
+    pub fn devices(&self) -> impl Iterator<Item = &dyn Device> {
+        let builtins: [&dyn Device; 3] = [&self.keyboard, &self.display, &self.machine_control];
+        let devices = self.devices.iter().map(|(_, device)| device.as_ref());
+        builtins.into_iter().chain(devices)
+    }
+
+    pub fn devices_mut(&mut self) -> impl Iterator<Item = &mut (dyn Device + 'static)> {
+        let builtins: [&mut (dyn Device + 'static); 3] = [
+            &mut self.keyboard,
+            &mut self.display,
+            &mut self.machine_control,
+        ];
+        let devices = self.devices.iter_mut().map(|(_, device)| device.as_mut());
+        builtins.into_iter().chain(devices)
+    }
+
+    /// Lets every device run between instructions and collects their
+    /// interrupt requests.
+    pub fn tick(&mut self, input: &mut dyn Read, interrupts: &mut InterruptController) {
+        for device in self.devices_mut() {
+            device.tick(input);
+            if let Some(interrupt) = device.interrupt() {
+                interrupts.raise(interrupt);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+
+    use crate::lc3::interrupt::{Interrupt, InterruptController};
+
+    use super::{Bus, Device};
+
+    #[derive(Default)]
+    struct Counter {
+        value: u16,
+    }
+
+    impl Device for Counter {
+        fn read(&mut self, _address: u16, _input: &mut dyn Read) -> u16 {
+            self.value
+        }
+
+        fn write(&mut self, _address: u16, value: u16) {
+            self.value = value;
+        }
+
+        fn peek(&self, _address: u16) -> u16 {
+            self.value
+        }
+
+        fn tick(&mut self, _input: &mut dyn Read) {
+            self.value += 1;
+        }
+
+        fn interrupt(&self) -> Option<Interrupt> {
+            (self.value > 1).then_some(Interrupt {
+                priority: 1,
+                vector: 0x81,
+            })
+        }
+    }
+
+    #[test]
+    fn test_route_and_tick() {
+        let mut bus = Bus::default();
+        let mut interrupts = InterruptController::default();
+        let mut input = std::io::empty();
+        bus.attach(0xFE10..=0xFE11, Box::<Counter>::default());
+
+        assert!(bus.device_mut(0xFE0F).is_none());
+        assert!(bus.device_mut(0xFE04).is_some());
+        bus.device_mut(0xFE11).unwrap().write(0xFE11, 1);
+        assert_eq!(1, bus.device_mut(0xFE10).unwrap().read(0xFE10, &mut input));
+        assert_eq!(1, bus.device(0xFE11).unwrap().peek(0xFE11));
+
+        bus.tick(&mut input, &mut interrupts);
+        assert_eq!(2, bus.device_mut(0xFE10).unwrap().read(0xFE10, &mut input));
+        assert!(interrupts.take(0).is_some());
+    }
+}
@@ -0,0 +1,126 @@
+use std::{fmt::Display, io::Read};
+
+use super::{error::Error, instructions::Instructions};
+
+/// A disassembled word together with where it is stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub address: u16,
+    pub word: u16,
+    pub text: String,
+}
+
+impl Display for Line {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "x{:04X}  x{:04X}  {}",
+            self.address, self.word, self.text
+        )
+    }
+}
+
+/// Disassembles `word` as if it were stored at `address`.
+pub fn disassemble(address: u16, word: u16) -> String {
+    match Instructions::try_from(word) {
+        Ok(instruction) => instruction.at(address).to_string(),
+        Err(_) => format!(".FILL x{word:04X}"),
+    }
+}
+
+pub fn disassemble_words(origin: u16, words: &[u16]) -> Vec<Line> {
+    let mut address = origin;
+    words
+        .iter()
+        .map(|&word| {
+            let line = Line {
+                address,
+                word,
+                text: disassemble(address, word),
+            };
+            address = address.wrapping_add(1);
+            line
+        })
+        .collect()
+}
+
+/// Disassembles an object file in the format `LittleComputer3::load_program`
+/// reads.
+pub fn disassemble_object(mut source: impl Read) -> Result<Vec<Line>, Error> {
+    let mut bytes = Vec::new();
+    source.read_to_end(&mut bytes)?;
+    if bytes.len() < 2 {
+        return Err(Error::IoError(std::io::ErrorKind::UnexpectedEof.into()));
+    }
+
+    let words: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+        .collect();
+    Ok(disassemble_words(words[0], &words[1..]))
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::lc3::{asm::assemble, instructions::Instructions};
+
+    use super::{disassemble, disassemble_object, disassemble_words};
+
+    #[test]
+    fn test_disassemble() {
+        assert_eq!("ADD R0, R1, #-1", disassemble(0x3000, 0x107F));
+        assert_eq!("AND R3, R4, R7", disassemble(0x3000, 0x5707));
+        assert_eq!("NOT R5, R6", disassemble(0x3000, 0x9BBF));
+        assert_eq!("BRnz x3010", disassemble(0x3000, 0x0C0F));
+        assert_eq!("BRp x2FFA", disassemble(0x3000, 0x03F9));
+        assert_eq!(".FILL x0000", disassemble(0x3000, 0x0000));
+        assert_eq!(".FILL x0005", disassemble(0x3000, 0x0005));
+        assert_eq!("JMP R2", disassemble(0x3000, 0xC080));
+        assert_eq!("RET", disassemble(0x3000, 0xC1C0));
+        assert_eq!("JSR x2C01", disassemble(0x3000, 0x4C00));
+        assert_eq!("JSRR R3", disassemble(0x3000, 0x40C0));
+        assert_eq!("LD R1, x0000", disassemble(0xFFFF, 0x2200));
+        assert_eq!("LDI R1, x3000", disassemble(0x3000, 0xA3FF));
+        assert_eq!("LDR R1, R2, #-32", disassemble(0x3000, 0x62A0));
+        assert_eq!("LEA R7, x3100", disassemble(0x3000, 0xEEFF));
+        assert_eq!("ST R1, x3001", disassemble(0x3000, 0x3200));
+        assert_eq!("STI R1, x2F01", disassemble(0x3000, 0xB300));
+        assert_eq!("STR R1, R2, #31", disassemble(0x3000, 0x729F));
+        assert_eq!("TRAP x25 ; HALT", disassemble(0x3000, 0xF025));
+        assert_eq!("TRAP x26", disassemble(0x3000, 0xF026));
+        assert_eq!("RTI", disassemble(0x3000, 0x8000));
+        assert_eq!("RES", disassemble(0x3000, 0xD000));
+
+        let instruction = Instructions::try_from(0x0C0F).unwrap();
+        assert_eq!("BRnz #15", instruction.to_string());
+    }
+
+    #[test]
+    fn test_disassemble_object() {
+        let program = assemble(".ORIG x3000\nLOOP ADD R0, R0, #1\nBRp LOOP\nHALT").unwrap();
+        let lines = disassemble_object(Cursor::new(program.to_bytes())).unwrap();
+        let lines: Vec<String> = lines.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            vec![
+                "x3000  x1021  ADD R0, R0, #1",
+                "x3001  x03FE  BRp x3000",
+                "x3002  xF025  TRAP x25 ; HALT",
+            ],
+            lines
+        );
+    }
+
+    #[test]
+    fn test_reassemble() {
+        let words = [0x0000, 0x0005, 0x1021, 0xF025];
+        let lines = disassemble_words(0x3000, &words);
+        let text: Vec<&str> = lines.iter().map(|line| line.text.as_str()).collect();
+        let source = format!(".ORIG x3000\n{}\n.END\n", text.join("\n"));
+        let program = assemble(&source).unwrap();
+        let mut expected = vec![0x30, 0x00];
+        expected.extend(words.iter().flat_map(|word| word.to_be_bytes()));
+        assert_eq!(expected, program.to_bytes());
+    }
+}
@@ -0,0 +1,123 @@
+use std::io::Read;
+
+use super::{device::Device, error::Error, memory::MemoryMappedReg, snapshot::Decoder};
+
+const DSR_READY: u16 = 1 << 15;
+
+/// Display behind DSR/DDR. Characters written to DDR are collected until the
+/// machine forwards them to its output. With a latency the display reports
+/// busy in DSR for that many instructions after each character.
+#[derive(Debug, Default)]
+pub struct Display {
+    latency: u32,
+    busy: u32,
+    data: u16,
+    output: Vec<u8>,
+}
+
+impl Display {
+    pub fn with_latency(latency: u32) -> Self {
+        Self {
+            latency,
+            ..Default::default()
+        }
+    }
+
+    pub fn set_latency(&mut self, latency: u32) {
+        self.latency = latency;
+        self.busy = self.busy.min(latency);
+    }
+
+    pub fn status(&self) -> u16 {
+        if self.busy == 0 {
+            DSR_READY
+        } else {
+            0
+        }
+    }
+
+    pub fn data(&self) -> u16 {
+        self.data
+    }
+
+    pub fn write_data(&mut self, value: u16) {
+        self.data = value;
+        self.output.push(value as u8);
+        self.busy = self.latency;
+    }
+
+    /// Returns the characters written since the last call.
+    pub fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.output)
+    }
+}
+
+impl Device for Display {
+    fn read(&mut self, address: u16, _input: &mut dyn Read) -> u16 {
+        self.peek(address)
+    }
+
+    fn write(&mut self, address: u16, value: u16) {
+        if address == MemoryMappedReg::Ddr as u16 {
+            self.write_data(value);
+        }
+    }
+
+    fn peek(&self, address: u16) -> u16 {
+        if address == MemoryMappedReg::Dsr as u16 {
+            self.status()
+        } else {
+            self.data
+        }
+    }
+
+    fn tick(&mut self, _input: &mut dyn Read) {
+        self.busy = self.busy.saturating_sub(1);
+    }
+
+    fn save(&self) -> Vec<u8> {
+        let mut state = self.busy.to_be_bytes().to_vec();
+        state.extend(self.data.to_be_bytes());
+        state
+    }
+
+    fn load(&mut self, state: &[u8]) -> Result<(), Error> {
+        let mut state = Decoder::new(state);
+        self.busy = state.u32()?;
+        self.data = state.u16()?;
+        state.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lc3::device::Device;
+
+    use super::{Display, DSR_READY};
+
+    #[test]
+    fn test_write_data() {
+        let mut display = Display::default();
+        assert_eq!(DSR_READY, display.status());
+
+        display.write_data('H' as u16);
+        display.write_data('i' as u16);
+        assert_eq!(DSR_READY, display.status());
+        assert_eq!('i' as u16, display.data());
+        assert_eq!(b"Hi".to_vec(), display.take_output());
+        assert!(display.take_output().is_empty());
+    }
+
+    #[test]
+    fn test_latency() {
+        let mut display = Display::with_latency(2);
+        assert_eq!(DSR_READY, display.status());
+
+        display.write_data('A' as u16);
+        assert_eq!(0, display.status());
+        display.tick(&mut std::io::empty());
+        assert_eq!(0, display.status());
+        display.tick(&mut std::io::empty());
+        assert_eq!(DSR_READY, display.status());
+    }
+}
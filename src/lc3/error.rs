@@ -5,6 +5,12 @@ pub enum Error {
     UnknownRegister(u16),
     UnknownInstruction(u16),
     UnknownTrapRoutine(u16),
+    OutOfRange { value: u16, bits: u8 },
+    MachineHalted,
+    InputExhausted,
+    Paused,
+    InvalidSnapshot(String),
+    InvalidRecording(String),
     IoError(std::io::Error),
 }
 
@@ -18,6 +24,14 @@ impl Display for Error {
             Error::UnknownTrapRoutine(routine) => {
                 write!(f, "'{:#X}' is not a known trap routine", routine)
             }
+            Error::OutOfRange { value, bits } => {
+                write!(f, "'{}' does not fit in {} bits", *value as i16, bits)
+            }
+            Error::MachineHalted => write!(f, "the machine is halted"),
+            Error::InputExhausted => write!(f, "the input is exhausted"),
+            Error::Paused => write!(f, "the machine was paused"),
+            Error::InvalidSnapshot(reason) => write!(f, "invalid snapshot: {}", reason),
+            Error::InvalidRecording(reason) => write!(f, "invalid input recording: {}", reason),
             Error::IoError(io_error) => write!(f, "IO error: {}", io_error),
         }
     }
@@ -27,6 +41,11 @@ impl std::error::Error for Error {}
 
 impl From<std::io::Error> for Error {
     fn from(e: std::io::Error) -> Self {
+        // a read given up because the machine was paused
+        let inner = e.get_ref().and_then(|inner| inner.downcast_ref::<Error>());
+        if matches!(inner, Some(Error::Paused)) {
+            return Error::Paused;
+        }
         Error::IoError(e)
     }
 }
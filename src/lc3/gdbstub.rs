@@ -0,0 +1,670 @@
+use std::{
+    collections::BTreeSet,
+    io::{BufRead, BufReader, ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::Duration,
+};
+
+use crate::vm::{memory::MemoryTrait, registers::RegistersTrait};
+
+use super::{
+    console::Hotkey,
+    debugger::{WatchKind, Watchpoint},
+    error::Error,
+    machine::LittleComputer3,
+    registers::RegistersEnum,
+};
+
+/// How often gdb's connection is checked for an interrupt while running.
+const WATCH_INTERVAL: Duration = Duration::from_millis(10);
+
+const REGISTERS: [RegistersEnum; 10] = [
+    RegistersEnum::R0,
+    RegistersEnum::R1,
+    RegistersEnum::R2,
+    RegistersEnum::R3,
+    RegistersEnum::R4,
+    RegistersEnum::R5,
+    RegistersEnum::R6,
+    RegistersEnum::R7,
+    RegistersEnum::ProgramCounter,
+    RegistersEnum::ProcessorStatus,
+];
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.lc3.core">
+    <reg name="r0" bitsize="16" regnum="0"/>
+    <reg name="r1" bitsize="16"/>
+    <reg name="r2" bitsize="16"/>
+    <reg name="r3" bitsize="16"/>
+    <reg name="r4" bitsize="16"/>
+    <reg name="r5" bitsize="16"/>
+    <reg name="r6" bitsize="16" type="data_ptr"/>
+    <reg name="r7" bitsize="16"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="psr" bitsize="16"/>
+  </feature>
+</target>
+"#;
+
+/// Waits for a single gdb connection on `address` and serves it.
+pub fn listen<A>(address: A, machine: &mut LittleComputer3) -> Result<(), Error>
+where
+    A: ToSocketAddrs,
+{
+    let listener = TcpListener::bind(address)?;
+    let (stream, _) = listener.accept()?;
+    GdbStub::new(stream)?.serve(machine)
+}
+
+/// Speaks the GDB Remote Serial Protocol for a [`LittleComputer3`].
+///
+/// The registers are R0 to R7, PC and PSR. LC-3 memory is word addressed,
+/// so gdb sees a byte addressed space in which word `a` occupies bytes `2a`
+/// and `2a + 1`, high byte first like in object files. Registers are sent
+/// high byte first as well.
+pub struct GdbStub {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    no_ack: bool,
+    breakpoints: BTreeSet<u16>,
+    watchpoints: Vec<Watchpoint>,
+}
+
+impl GdbStub {
+    pub fn new(stream: TcpStream) -> Result<Self, Error> {
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            writer: stream.try_clone()?,
+            reader: BufReader::new(stream),
+            no_ack: false,
+            breakpoints: BTreeSet::new(),
+            watchpoints: Vec::new(),
+        })
+    }
+
+    /// Answers requests until gdb detaches, kills the target or disconnects.
+    pub fn serve(&mut self, machine: &mut LittleComputer3) -> Result<(), Error> {
+        while let Some(packet) = self.read_packet()? {
+            let reply = match packet.as_str() {
+                "D" => {
+                    self.send("OK")?;
+                    return Ok(());
+                }
+                "k" => return Ok(()),
+                _ => self.handle(machine, &packet)?,
+            };
+            self.send(&reply)?;
+            if packet == "QStartNoAckMode" {
+                self.no_ack = true;
+            }
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, machine: &mut LittleComputer3, packet: &str) -> Result<String, Error> {
+        let Some((command, arguments)) = packet.split_at_checked(1) else {
+            // an empty packet is not a request we support
+            return Ok(String::new());
+        };
+        let reply = match command {
+            "?" | "\x03" => "S05".to_string(),
+            "g" => REGISTERS
+                .iter()
+                .map(|register| format!("{:04x}", machine.registers().get(*register)))
+                .collect(),
+            "G" => match decode_words(arguments) {
+                Some(values) if values.len() == REGISTERS.len() => {
+                    for (register, value) in REGISTERS.iter().zip(values) {
+                        machine.registers_mut().set(*register, value);
+                    }
+                    "OK".to_string()
+                }
+                _ => "E01".to_string(),
+            },
+            "p" => match parse_hex(arguments).and_then(|index| REGISTERS.get(index)) {
+                Some(register) => format!("{:04x}", machine.registers().get(*register)),
+                None => "E01".to_string(),
+            },
+            "P" => {
+                let assignment = arguments.split_once('=').and_then(|(index, value)| {
+                    let register = REGISTERS.get(parse_hex(index)?)?;
+                    Some((*register, *decode_words(value)?.first()?))
+                });
+                match assignment {
+                    Some((register, value)) => {
+                        machine.registers_mut().set(register, value);
+                        "OK".to_string()
+                    }
+                    None => "E01".to_string(),
+                }
+            }
+            "m" => match parse_range(arguments) {
+                Some((start, length)) => (start..start + length)
+                    .map(|address| format!("{:02x}", read_byte(machine, address)))
+                    .collect(),
+                None => "E01".to_string(),
+            },
+            "M" => {
+                let write = arguments.split_once(':').and_then(|(range, data)| {
+                    let (start, length) = parse_range(range)?;
+                    let bytes = decode_bytes(data)?;
+                    (bytes.len() == length).then_some((start, bytes))
+                });
+                match write {
+                    Some((start, bytes)) => {
+                        for (address, byte) in (start..).zip(bytes) {
+                            write_byte(machine, address, byte);
+                        }
+                        "OK".to_string()
+                    }
+                    None => "E01".to_string(),
+                }
+            }
+            "s" | "c" => {
+                if let Some(address) = parse_hex(arguments) {
+                    machine.registers_mut().set_pc((address / 2) as u16);
+                }
+                self.resume(machine, command == "s")?
+            }
+            "Z" | "z" => match self.set_point(arguments, command == "Z") {
+                Some(reply) => reply.to_string(),
+                None => "E01".to_string(),
+            },
+            "H" => "OK".to_string(),
+            "q" | "Q" => self.query(packet),
+            _ => String::new(),
+        };
+        Ok(reply)
+    }
+
+    fn query(&self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            return "PacketSize=1000;QStartNoAckMode+;swbreak+;hwbreak+;qXfer:features:read+"
+                .to_string();
+        }
+        if let Some(window) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let Some((offset, length)) = parse_range(window) else {
+                return "E01".to_string();
+            };
+            let rest = TARGET_XML
+                .get(offset.min(TARGET_XML.len())..)
+                .unwrap_or_default();
+            return match rest.get(..length) {
+                Some(chunk) if chunk.len() < rest.len() => format!("m{chunk}"),
+                _ => format!("l{rest}"),
+            };
+        }
+        match packet {
+            "QStartNoAckMode" => "OK",
+            "qAttached" => "1",
+            "qC" => "QC1",
+            "qfThreadInfo" => "m1",
+            "qsThreadInfo" => "l",
+            _ => "",
+        }
+        .to_string()
+    }
+
+    /// Handles `Z` and `z` packets: `type,address,kind`.
+    fn set_point(&mut self, arguments: &str, insert: bool) -> Option<&'static str> {
+        let mut fields = arguments.split(',');
+        let kind = fields.next()?;
+        let address = parse_hex(fields.next()?)?;
+        let length = parse_hex(fields.next()?)?.max(1);
+        let start = u16::try_from(address / 2).ok()?;
+        let end = u16::try_from(address.checked_add(length - 1)? / 2).ok()?;
+
+        let kind = match kind {
+            "0" | "1" => {
+                if insert {
+                    self.breakpoints.insert(start);
+                } else {
+                    self.breakpoints.remove(&start);
+                }
+                return Some("OK");
+            }
+            "2" => WatchKind::Write,
+            "3" => WatchKind::Read,
+            "4" => WatchKind::Access,
+            _ => return Some(""),
+        };
+        let watchpoint = Watchpoint {
+            range: start..=end,
+            kind,
+        };
+        if insert {
+            self.watchpoints.push(watchpoint);
+        } else {
+            self.watchpoints.retain(|existing| *existing != watchpoint);
+        }
+        Some("OK")
+    }
+
+    /// Runs the machine and returns the stop reply. An interrupt from gdb
+    /// presses the machine's pause key, so it also stops a TRAP waiting for
+    /// a key.
+    fn resume(
+        &mut self,
+        machine: &mut LittleComputer3,
+        single_step: bool,
+    ) -> Result<String, Error> {
+        let pause = machine.pause().cloned().unwrap_or_default();
+        // an interrupt that came after the last stop
+        pause.take();
+        machine.set_pause(Some(pause.clone()));
+        machine.set_record_accesses(!self.watchpoints.is_empty());
+
+        let mut reply = None;
+        while !machine.is_halted() {
+            if self.interrupted()? {
+                return Ok("S02".to_string());
+            }
+            let stream = &self.writer;
+            stream.set_read_timeout(Some(WATCH_INTERVAL))?;
+            let running = AtomicBool::new(true);
+            let result = thread::scope(|scope| {
+                scope.spawn(|| watch_interrupt(stream, &running, &pause));
+                let result = machine.run_until(|step| {
+                    for access in &step.accesses {
+                        let watchpoint = self
+                            .watchpoints
+                            .iter()
+                            .find(|watchpoint| watchpoint.matches(access));
+                        if let Some(watchpoint) = watchpoint {
+                            let kind = match watchpoint.kind {
+                                WatchKind::Write => "watch",
+                                WatchKind::Read => "rwatch",
+                                WatchKind::Access => "awatch",
+                            };
+                            reply = Some(format!("T05{kind}:{:x};", access.address as u32 * 2));
+                            return true;
+                        }
+                    }
+                    if self.breakpoints.contains(&step.pc_after) {
+                        reply = Some("T05swbreak:;".to_string());
+                        return true;
+                    }
+                    single_step
+                });
+                running.store(false, Ordering::Relaxed);
+                result
+            });
+            stream.set_read_timeout(None)?;
+            if result.is_err() {
+                // the program cannot continue, e.g. because its input ran out
+                return Ok("X06".to_string());
+            }
+
+            if let Some(reply) = reply {
+                return Ok(reply);
+            }
+            if single_step {
+                return Ok("S05".to_string());
+            }
+        }
+
+        let exit_code = machine.registers().get(RegistersEnum::R0) & 0xFF;
+        Ok(format!("W{exit_code:02x}"))
+    }
+
+    /// Whether gdb sent an interrupt (Ctrl-C) while the machine was running.
+    fn interrupted(&mut self) -> Result<bool, Error> {
+        self.reader.get_ref().set_nonblocking(true)?;
+        let available = match self.reader.fill_buf() {
+            Ok(buffer) => buffer.first().copied(),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => None,
+            Err(e) => {
+                self.reader.get_ref().set_nonblocking(false)?;
+                return Err(e.into());
+            }
+        };
+        self.reader.get_ref().set_nonblocking(false)?;
+
+        if available == Some(0x03) {
+            self.reader.consume(1);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    fn read_byte(&mut self) -> Result<Option<u8>, Error> {
+        let mut byte = [0];
+        match self.reader.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    /// Reads the next packet, skipping acknowledgements. An interrupt request
+    /// outside of a packet is returned as `"\x03"`.
+    fn read_packet(&mut self) -> Result<Option<String>, Error> {
+        loop {
+            match self.read_byte()? {
+                None => return Ok(None),
+                Some(0x03) => return Ok(Some("\x03".to_string())),
+                Some(b'$') => {}
+                Some(_) => continue,
+            }
+
+            let mut data = Vec::new();
+            self.reader.read_until(b'#', &mut data)?;
+            if data.pop() != Some(b'#') {
+                return Ok(None);
+            }
+            let mut checksum = [0; 2];
+            self.reader.read_exact(&mut checksum)?;
+
+            let expected = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+            if !self.no_ack {
+                let valid = expected == Some(checksum_of(&data));
+                self.writer.write_all(if valid { b"+" } else { b"-" })?;
+                if !valid {
+                    continue;
+                }
+            }
+            return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+        }
+    }
+
+    fn send(&mut self, data: &str) -> Result<(), Error> {
+        let packet = format!("${data}#{:02x}", checksum_of(data.as_bytes()));
+        loop {
+            self.writer.write_all(packet.as_bytes())?;
+            self.writer.flush()?;
+            if self.no_ack {
+                return Ok(());
+            }
+            match self.read_byte()? {
+                Some(b'-') => continue,
+                _ => return Ok(()),
+            }
+        }
+    }
+}
+
+/// Presses `pause` once gdb sends an interrupt (Ctrl-C), leaving the byte
+/// for [`GdbStub::interrupted`]. Gives up at anything else gdb sends.
+fn watch_interrupt(stream: &TcpStream, running: &AtomicBool, pause: &Hotkey) {
+    let mut byte = [0];
+    while running.load(Ordering::Relaxed) {
+        match stream.peek(&mut byte) {
+            Ok(1) if byte[0] == 0x03 => {
+                pause.press();
+                return;
+            }
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            _ => return,
+        }
+    }
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn parse_hex(text: &str) -> Option<usize> {
+    usize::from_str_radix(text, 16).ok()
+}
+
+/// Parses `address,length` and checks it lies within the 128 KiB byte space.
+fn parse_range(text: &str) -> Option<(usize, usize)> {
+    let (start, length) = text.split_once(',')?;
+    let (start, length) = (parse_hex(start)?, parse_hex(length)?);
+    (start.checked_add(length)? <= 2 << 16).then_some((start, length))
+}
+
+fn decode_bytes(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(text.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+fn decode_words(text: &str) -> Option<Vec<u16>> {
+    let bytes = decode_bytes(text)?;
+    if !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    Some(
+        bytes
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect(),
+    )
+}
+
+fn read_byte(machine: &LittleComputer3, address: usize) -> u8 {
+    let [high, low] = machine.memory().peek((address / 2) as u16).to_be_bytes();
+    if address.is_multiple_of(2) {
+        high
+    } else {
+        low
+    }
+}
+
+fn write_byte(machine: &mut LittleComputer3, address: usize, byte: u8) {
+    let word = (address / 2) as u16;
+    let [mut high, mut low] = machine.memory().peek(word).to_be_bytes();
+    if address.is_multiple_of(2) {
+        high = byte;
+    } else {
+        low = byte;
+    }
+    machine
+        .memory_mut()
+        .write(word, u16::from_be_bytes([high, low]));
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{BufRead, BufReader, Cursor, Read, Write},
+        net::{TcpListener, TcpStream},
+        thread,
+        time::Duration,
+    };
+
+    use crate::{
+        lc3::{
+            asm::assemble, machine::LittleComputer3, registers::RegistersEnum, replay::Recording,
+        },
+        vm::registers::RegistersTrait,
+    };
+
+    use super::{checksum_of, GdbStub};
+
+    const SOURCE: &str = "
+        .ORIG x3000
+START   AND R0, R0, #0
+LOOP    ADD R0, R0, #1
+        ST R0, DATA
+        ADD R1, R0, #-3
+        BRn LOOP
+        HALT
+DATA    .FILL x1234
+";
+
+    /// A minimal RSP client that sends every request and collects the replies.
+    fn request(stream: &mut BufReader<TcpStream>, packet: &str) -> String {
+        let packet = format!("${packet}#{:02x}", checksum_of(packet.as_bytes()));
+        stream.get_mut().write_all(packet.as_bytes()).unwrap();
+
+        let mut ack = [0];
+        stream.read_exact(&mut ack).unwrap();
+        assert_eq!(b'+', ack[0]);
+        reply(stream)
+    }
+
+    fn reply(stream: &mut BufReader<TcpStream>) -> String {
+        let mut reply = Vec::new();
+        stream.read_until(b'$', &mut reply).unwrap();
+        reply.clear();
+        stream.read_until(b'#', &mut reply).unwrap();
+        reply.pop();
+        let mut checksum = [0; 2];
+        stream.read_exact(&mut checksum).unwrap();
+        assert_eq!(
+            format!("{:02x}", checksum_of(&reply)).as_bytes(),
+            checksum.as_slice()
+        );
+        stream.get_mut().write_all(b"+").unwrap();
+        String::from_utf8(reply).unwrap()
+    }
+
+    fn session(requests: &'static [&'static str]) -> (LittleComputer3, Vec<String>) {
+        let program = assemble(SOURCE).unwrap();
+        let mut lc3 = LittleComputer3::builder()
+            .input(Cursor::new([]))
+            .output(Vec::new())
+            .build()
+            .unwrap();
+        lc3.load_program(Cursor::new(program.to_bytes())).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = BufReader::new(TcpStream::connect(address).unwrap());
+            requests
+                .iter()
+                .map(|packet| request(&mut stream, packet))
+                .collect::<Vec<_>>()
+        });
+
+        let (stream, _) = listener.accept().unwrap();
+        GdbStub::new(stream).unwrap().serve(&mut lc3).unwrap();
+        (lc3, client.join().unwrap())
+    }
+
+    #[test]
+    fn test_registers() {
+        let (lc3, replies) = session(&["?", "g", "P1=abcd", "p1", "p8", "p9", "pa", "D"]);
+        assert_eq!(
+            vec![
+                "S05",
+                "0000000000000000000000000000000030008000",
+                "OK",
+                "abcd",
+                "3000",
+                "8000",
+                "E01",
+                "OK",
+            ],
+            replies
+        );
+        assert_eq!(0xABCD, lc3.registers().get(RegistersEnum::R1));
+    }
+
+    #[test]
+    fn test_memory() {
+        // DATA is word x3006, i.e. bytes x600c and x600d
+        let (lc3, replies) = session(&["m600c,2", "M600d,1:78", "m600b,4", "D"]);
+        assert_eq!(vec!["1234", "OK", "25127800", "OK"], replies);
+        assert_eq!(0x1278, lc3.memory().peek(0x3006));
+    }
+
+    #[test]
+    fn test_step_and_breakpoint() {
+        let (lc3, replies) = session(&[
+            "s",
+            "p8",
+            "Z0,6008,2",
+            "c",
+            "p0",
+            "c",
+            "z0,6008,2",
+            "c",
+            "D",
+        ]);
+        assert_eq!(
+            vec![
+                "S05",
+                "3001",
+                "OK",
+                "T05swbreak:;",
+                "0001",
+                "T05swbreak:;",
+                "OK",
+                "W03",
+                "OK"
+            ],
+            replies
+        );
+        assert!(lc3.is_halted());
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let (_, replies) = session(&["Z2,600c,2", "c", "p0", "z2,600c,2", "Z3,600c,2", "c", "D"]);
+        assert_eq!(
+            vec!["OK", "T05watch:600c;", "0001", "OK", "OK", "W03", "OK"],
+            replies
+        );
+    }
+
+    #[test]
+    fn test_queries() {
+        let (_, replies) = session(&[
+            "qSupported:swbreak+",
+            "qAttached",
+            "vMustReplyEmpty",
+            "qXfer:features:read:target.xml:0,20",
+            "D",
+        ]);
+        assert!(replies[0].contains("swbreak+"));
+        assert_eq!("1", replies[1]);
+        assert_eq!("", replies[2]);
+        assert_eq!("m<?xml version=\"1.0\"?>\n<!DOCTYPE ", replies[3]);
+    }
+
+    #[test]
+    fn test_interrupt() {
+        // .ORIG x3000, GETC, HALT
+        const PROGRAM: [u8; 6] = [0x30, 0x00, 0xF0, 0x20, 0xF0, 0x25];
+        let mut lc3 = LittleComputer3::builder()
+            .output(Vec::new())
+            .build()
+            .unwrap();
+        lc3.load_program(Cursor::new(PROGRAM)).unwrap();
+        // no key is ever pressed
+        lc3.replay_input(Recording::default());
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = BufReader::new(TcpStream::connect(address).unwrap());
+            stream.get_mut().write_all(b"$c#63").unwrap();
+            let mut ack = [0];
+            stream.read_exact(&mut ack).unwrap();
+            thread::sleep(Duration::from_millis(50));
+            stream.get_mut().write_all(&[0x03]).unwrap();
+            let stopped = reply(&mut stream);
+            (
+                stopped,
+                request(&mut stream, "p8"),
+                request(&mut stream, "D"),
+            )
+        });
+
+        let (stream, _) = listener.accept().unwrap();
+        GdbStub::new(stream).unwrap().serve(&mut lc3).unwrap();
+        let replies = client.join().unwrap();
+        // GETC runs again once the program continues
+        assert_eq!(("S02".into(), "3000".into(), "OK".into()), replies);
+    }
+
+    #[test]
+    fn test_malformed_packets() {
+        let (_, replies) = session(&["", "Z2,ffffffffffffffff,2", "z3,0,0", "D"]);
+        assert_eq!(vec!["", "E01", "OK", "OK"], replies);
+    }
+}
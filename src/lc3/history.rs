@@ -0,0 +1,170 @@
+use std::collections::VecDeque;
+
+use super::{
+    memory::{Access, AccessKind},
+    registers::Registers,
+};
+
+/// Everything needed to undo one executed instruction.
+#[derive(Clone, Debug)]
+pub struct Undo {
+    cycle: u64,
+    pc: u16,
+    word: u16,
+    registers: Registers,
+    writes: Vec<Access>,
+}
+
+impl Undo {
+    pub(crate) fn new(cycle: u64, pc: u16, registers: Registers, accesses: &[Access]) -> Self {
+        let word = accesses
+            .iter()
+            .find(|access| access.kind == AccessKind::Fetch)
+            .map_or(0, |access| access.value);
+        let writes = accesses
+            .iter()
+            .filter(|access| access.kind == AccessKind::Write)
+            .copied()
+            .collect();
+        Self {
+            cycle,
+            pc,
+            word,
+            registers,
+            writes,
+        }
+    }
+
+    /// The cycle count once the instruction had executed.
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+
+    /// Where the instruction was fetched from.
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    /// The instruction word as it was fetched.
+    pub fn word(&self) -> u16 {
+        self.word
+    }
+
+    /// The registers before the instruction.
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    /// The memory writes of the instruction, with the values they replaced.
+    pub fn writes(&self) -> &[Access] {
+        &self.writes
+    }
+}
+
+/// Undo log of the most recently executed instructions. Once `capacity`
+/// instructions are recorded, the oldest is dropped for each new one.
+#[derive(Clone, Debug, Default)]
+pub struct History {
+    entries: VecDeque<Undo>,
+    capacity: usize,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub(crate) fn push(&mut self, undo: Undo) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(undo);
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<Undo> {
+        self.entries.pop_back()
+    }
+
+    /// The most recent instruction that wrote `address` and its write.
+    pub fn last_write(&self, address: u16) -> Option<(&Undo, &Access)> {
+        self.entries.iter().rev().find_map(|undo| {
+            let write = undo
+                .writes
+                .iter()
+                .rev()
+                .find(|access| access.address == address)?;
+            Some((undo, write))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lc3::{
+        memory::{Access, AccessKind},
+        registers::Registers,
+    };
+
+    use super::{History, Undo};
+
+    fn undo(cycle: u64, writes: &[(u16, u16)]) -> Undo {
+        let accesses: Vec<_> = writes
+            .iter()
+            .map(|(address, value)| Access {
+                kind: AccessKind::Write,
+                address: *address,
+                old: 0,
+                value: *value,
+            })
+            .collect();
+        Undo::new(cycle, 0x3000, Registers::default(), &accesses)
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut history = History::new(2);
+        for cycle in 1..=3 {
+            history.push(undo(cycle, &[]));
+        }
+        assert_eq!(2, history.len());
+        assert_eq!(3, history.pop().unwrap().cycle());
+        assert_eq!(2, history.pop().unwrap().cycle());
+        assert!(history.pop().is_none());
+
+        let mut disabled = History::new(0);
+        disabled.push(undo(1, &[]));
+        assert!(disabled.is_empty());
+    }
+
+    #[test]
+    fn test_last_write() {
+        let mut history = History::new(8);
+        history.push(undo(1, &[(0x4000, 1)]));
+        history.push(undo(2, &[(0x4001, 2)]));
+        history.push(undo(3, &[(0x4000, 3), (0x4000, 4)]));
+        history.push(undo(4, &[]));
+
+        let (undo, write) = history.last_write(0x4000).unwrap();
+        assert_eq!((3, 4), (undo.cycle(), write.value));
+        assert_eq!(2, history.last_write(0x4001).unwrap().0.cycle());
+        assert!(history.last_write(0x4002).is_none());
+    }
+}
@@ -1,12 +1,30 @@
+use std::fmt::Display;
+use std::io::ErrorKind;
 use std::io::Read;
 use std::io::Write;
-use std::process;
-
-use crate::vm::{instructions::InstructionsTrait, memory::MemoryTrait, registers::RegistersTrait};
-
-use super::{error::Error, registers::RegistersEnum};
-
-#[derive(Debug)]
+use std::time::Duration;
+
+use crate::vm::{
+    instructions::{ExecutionState, InstructionsTrait},
+    memory::MemoryTrait,
+    registers::RegistersTrait,
+};
+
+use super::{
+    error::Error,
+    interrupt::{self, Exception},
+    keyboard::KBSR_READY,
+    memory::MemoryMappedReg,
+    registers::{RegistersEnum, PSR_PRIVILEGE},
+};
+
+/// Opcode names indexed by the top four bits of an instruction.
+pub const OPCODES: [&str; 16] = [
+    "BR", "ADD", "LD", "ST", "JSR", "AND", "LDR", "STR", "RTI", "NOT", "LDI", "STI", "JMP", "RES",
+    "LEA", "TRAP",
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Instructions {
     Add {
         destination: RegistersEnum,
@@ -65,26 +83,41 @@ pub enum Instructions {
     RTI,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum RegisterMode {
     Immediate(u16),
     Register(RegistersEnum),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum JumpType {
     Long(u16),
     Register(RegistersEnum),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum TrapRoutine {
-    GETC = 0x20,
+    GETC,
     OUT,
     PUTS,
     IN,
     PUTSP,
     HALT,
+    Custom(u8),
+}
+
+impl TrapRoutine {
+    pub fn vector(&self) -> u8 {
+        match self {
+            TrapRoutine::GETC => 0x20,
+            TrapRoutine::OUT => 0x21,
+            TrapRoutine::PUTS => 0x22,
+            TrapRoutine::IN => 0x23,
+            TrapRoutine::PUTSP => 0x24,
+            TrapRoutine::HALT => 0x25,
+            TrapRoutine::Custom(vector) => *vector,
+        }
+    }
 }
 
 impl TryFrom<u16> for TrapRoutine {
@@ -98,6 +131,7 @@ impl TryFrom<u16> for TrapRoutine {
             0x23 => Ok(TrapRoutine::IN),
             0x24 => Ok(TrapRoutine::PUTSP),
             0x25 => Ok(TrapRoutine::HALT),
+            0x00..=0xFF => Ok(TrapRoutine::Custom(value as u8)),
             _ => Err(Error::UnknownTrapRoutine(value)),
         }
     }
@@ -110,6 +144,55 @@ fn sign_extend(mut x: u16, bit_count: u8) -> u16 {
     x
 }
 
+/// The low `bit_count` bits of `x`, provided sign extending them gives `x` back.
+fn truncate(x: u16, bit_count: u8) -> Result<u16, Error> {
+    let field = x & !(0xFFFF << bit_count);
+    if sign_extend(field, bit_count) != x {
+        return Err(Error::OutOfRange {
+            value: x,
+            bits: bit_count,
+        });
+    }
+    Ok(field)
+}
+
+fn register(register: &RegistersEnum) -> Result<u16, Error> {
+    match *register as u16 {
+        index @ 0..=7 => Ok(index),
+        index => Err(Error::UnknownRegister(index)),
+    }
+}
+
+/// Reads the next key, taking one the keyboard already holds first and
+/// waiting for the input otherwise. Once the input is exhausted the keyboard
+/// decides whether a value is still returned.
+fn read_key<M, I>(memory: &mut M, input: &mut I) -> Result<u16, Error>
+where
+    M: MemoryTrait<ValueType = u16>,
+    I: Read,
+{
+    let mut buffer = [0; 1];
+    loop {
+        if memory.read(MemoryMappedReg::Kbsr as u16, input) & KBSR_READY != 0 {
+            return Ok(memory.read(MemoryMappedReg::Kbdr as u16, input));
+        }
+        match input.read(&mut buffer) {
+            Ok(0) => {
+                if memory.read(MemoryMappedReg::Kbsr as u16, input) & KBSR_READY != 0 {
+                    return Ok(memory.read(MemoryMappedReg::Kbdr as u16, input));
+                }
+                return Err(Error::InputExhausted);
+            }
+            Ok(_) => return Ok(buffer[0] as u16),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                std::thread::sleep(Duration::from_millis(1))
+            }
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
 impl TryFrom<u16> for Instructions {
     type Error = Error;
 
@@ -210,6 +293,221 @@ impl TryFrom<u16> for Instructions {
     }
 }
 
+impl Instructions {
+    /// Encodes the instruction into its machine word. Only R0 to R7 can be
+    /// encoded, and immediates and offsets have to fit their fields.
+    pub fn encode(&self) -> Result<u16, Error> {
+        let word = match self {
+            Instructions::Add {
+                destination,
+                source1,
+                source2,
+            } => {
+                0x1000 | register(destination)? << 9 | register(source1)? << 6 | source2.encode()?
+            }
+            Instructions::And {
+                destination,
+                source1,
+                source2,
+            } => {
+                0x5000 | register(destination)? << 9 | register(source1)? << 6 | source2.encode()?
+            }
+            Instructions::Branch {
+                pc_offset,
+                condition_flag,
+            } => {
+                if *condition_flag > 0x7 {
+                    return Err(Error::OutOfRange {
+                        value: *condition_flag,
+                        bits: 3,
+                    });
+                }
+                condition_flag << 9 | truncate(*pc_offset, 9)?
+            }
+            Instructions::Not {
+                destination,
+                source1,
+            } => 0x903F | register(destination)? << 9 | register(source1)? << 6,
+            Instructions::Jump { source } => 0xC000 | register(source)? << 6,
+            Instructions::JumpRegister(JumpType::Long(pc_offset)) => {
+                0x4800 | truncate(*pc_offset, 11)?
+            }
+            Instructions::JumpRegister(JumpType::Register(source)) => {
+                0x4000 | register(source)? << 6
+            }
+            Instructions::Load {
+                destination,
+                pc_offset,
+            } => 0x2000 | register(destination)? << 9 | truncate(*pc_offset, 9)?,
+            Instructions::LoadIndirect {
+                destination,
+                pc_offset,
+            } => 0xA000 | register(destination)? << 9 | truncate(*pc_offset, 9)?,
+            Instructions::LoadRegister {
+                destination,
+                source1,
+                offset,
+            } => {
+                0x6000
+                    | register(destination)? << 9
+                    | register(source1)? << 6
+                    | truncate(*offset, 6)?
+            }
+            Instructions::LoadEffectiveAddress {
+                destination,
+                pc_offset,
+            } => 0xE000 | register(destination)? << 9 | truncate(*pc_offset, 9)?,
+            Instructions::Store { source, pc_offset } => {
+                0x3000 | register(source)? << 9 | truncate(*pc_offset, 9)?
+            }
+            Instructions::StoreIndirect { source, pc_offset } => {
+                0xB000 | register(source)? << 9 | truncate(*pc_offset, 9)?
+            }
+            Instructions::StoreRegister {
+                source1,
+                source2,
+                offset,
+            } => 0x7000 | register(source1)? << 9 | register(source2)? << 6 | truncate(*offset, 6)?,
+            Instructions::Trap(routine) => 0xF000 | routine.vector() as u16,
+            Instructions::RES => 0xD000,
+            Instructions::RTI => 0x8000,
+        };
+        Ok(word)
+    }
+
+    /// Formats the instruction as if it were stored at `address`, so PC
+    /// relative operands show the address they refer to.
+    pub fn at(&self, address: u16) -> Disassembly<'_> {
+        Disassembly {
+            instruction: self,
+            address: Some(address),
+        }
+    }
+}
+
+/// Canonical LC-3 assembly for an instruction, see [`Instructions::at`].
+pub struct Disassembly<'a> {
+    instruction: &'a Instructions,
+    address: Option<u16>,
+}
+
+impl Disassembly<'_> {
+    fn target(&self, pc_offset: u16) -> String {
+        match self.address {
+            Some(address) => format!("x{:04X}", address.wrapping_add(1).wrapping_add(pc_offset)),
+            None => format!("#{}", pc_offset as i16),
+        }
+    }
+}
+
+impl Display for Disassembly<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.instruction {
+            Instructions::Add {
+                destination,
+                source1,
+                source2,
+            } => write!(f, "ADD {destination:?}, {source1:?}, {source2}"),
+            Instructions::And {
+                destination,
+                source1,
+                source2,
+            } => write!(f, "AND {destination:?}, {source1:?}, {source2}"),
+            Instructions::Not {
+                destination,
+                source1,
+            } => write!(f, "NOT {destination:?}, {source1:?}"),
+            // never branches, written as data so the output can be assembled
+            Instructions::Branch {
+                condition_flag: 0,
+                pc_offset,
+            } => write!(f, ".FILL x{:04X}", pc_offset & 0x01FF),
+            Instructions::Branch {
+                pc_offset,
+                condition_flag,
+            } => {
+                write!(f, "BR")?;
+                for (flag, name) in [(0x4, "n"), (0x2, "z"), (0x1, "p")] {
+                    if condition_flag & flag != 0 {
+                        write!(f, "{name}")?;
+                    }
+                }
+                write!(f, " {}", self.target(*pc_offset))
+            }
+            Instructions::Jump {
+                source: RegistersEnum::R7,
+            } => write!(f, "RET"),
+            Instructions::Jump { source } => write!(f, "JMP {source:?}"),
+            Instructions::JumpRegister(JumpType::Long(pc_offset)) => {
+                write!(f, "JSR {}", self.target(*pc_offset))
+            }
+            Instructions::JumpRegister(JumpType::Register(source)) => write!(f, "JSRR {source:?}"),
+            Instructions::Load {
+                destination,
+                pc_offset,
+            } => write!(f, "LD {destination:?}, {}", self.target(*pc_offset)),
+            Instructions::LoadIndirect {
+                destination,
+                pc_offset,
+            } => write!(f, "LDI {destination:?}, {}", self.target(*pc_offset)),
+            Instructions::LoadRegister {
+                destination,
+                source1,
+                offset,
+            } => write!(f, "LDR {destination:?}, {source1:?}, #{}", *offset as i16),
+            Instructions::LoadEffectiveAddress {
+                destination,
+                pc_offset,
+            } => write!(f, "LEA {destination:?}, {}", self.target(*pc_offset)),
+            Instructions::Store { source, pc_offset } => {
+                write!(f, "ST {source:?}, {}", self.target(*pc_offset))
+            }
+            Instructions::StoreIndirect { source, pc_offset } => {
+                write!(f, "STI {source:?}, {}", self.target(*pc_offset))
+            }
+            Instructions::StoreRegister {
+                source1,
+                source2,
+                offset,
+            } => write!(f, "STR {source1:?}, {source2:?}, #{}", *offset as i16),
+            Instructions::Trap(TrapRoutine::Custom(vector)) => write!(f, "TRAP x{vector:02X}"),
+            Instructions::Trap(routine) => {
+                write!(f, "TRAP x{:02X} ; {routine:?}", routine.vector())
+            }
+            Instructions::RES => write!(f, "RES"),
+            Instructions::RTI => write!(f, "RTI"),
+        }
+    }
+}
+
+impl Display for Instructions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Disassembly {
+            instruction: self,
+            address: None,
+        }
+        .fmt(f)
+    }
+}
+
+impl RegisterMode {
+    fn encode(&self) -> Result<u16, Error> {
+        match self {
+            RegisterMode::Immediate(value) => Ok(0x20 | truncate(*value, 5)?),
+            RegisterMode::Register(source) => register(source),
+        }
+    }
+}
+
+impl Display for RegisterMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegisterMode::Immediate(value) => write!(f, "#{}", *value as i16),
+            RegisterMode::Register(register) => write!(f, "{register:?}"),
+        }
+    }
+}
+
 impl InstructionsTrait for Instructions {
     type ValueType = u16;
     type InstructionSet = Instructions;
@@ -222,7 +520,7 @@ impl InstructionsTrait for Instructions {
         memory: &mut M,
         input: &mut I,
         output: &mut O,
-    ) -> Result<(), Self::Error>
+    ) -> Result<ExecutionState, Self::Error>
     where
         R: RegistersTrait<ValueType = Self::ValueType, RegisterSet = Self::RegisterSet>,
         M: MemoryTrait<ValueType = Self::ValueType>,
@@ -237,13 +535,13 @@ impl InstructionsTrait for Instructions {
             } => {
                 match source2 {
                     RegisterMode::Immediate(source2) => {
-                        let (result, _) = registers.get(*source1).overflowing_add(*source2);
+                        let result = registers.get(*source1).wrapping_add(*source2);
                         registers.set(*destination, result)
                     }
                     RegisterMode::Register(source2) => {
-                        let (result, _) = registers
+                        let result = registers
                             .get(*source1)
-                            .overflowing_add(registers.get(*source2));
+                            .wrapping_add(registers.get(*source2));
                         registers.set(*destination, result)
                     }
                 }
@@ -253,15 +551,39 @@ impl InstructionsTrait for Instructions {
                 destination,
                 pc_offset,
             } => {
-                let address = memory.read(
-                    registers.get(RegistersEnum::ProgramCounter) + pc_offset,
-                    input,
-                );
+                let address = registers
+                    .get(RegistersEnum::ProgramCounter)
+                    .wrapping_add(*pc_offset);
+                let address = memory.read(address, input);
                 registers.set(*destination, memory.read(address, input));
                 registers.update_flags(*destination);
             }
-            Instructions::RES => unreachable!(),
-            Instructions::RTI => unreachable!(),
+            Instructions::RES => {
+                let return_address = registers.get_pc().wrapping_sub(1);
+                interrupt::initiate(
+                    registers,
+                    memory,
+                    input,
+                    Exception::IllegalOpcode as u8,
+                    None,
+                    return_address,
+                );
+            }
+            Instructions::RTI => {
+                if registers.get(RegistersEnum::ProcessorStatus) & PSR_PRIVILEGE != 0 {
+                    let return_address = registers.get_pc().wrapping_sub(1);
+                    interrupt::initiate(
+                        registers,
+                        memory,
+                        input,
+                        Exception::PrivilegeModeViolation as u8,
+                        None,
+                        return_address,
+                    );
+                } else {
+                    interrupt::restore(registers, memory, input);
+                }
+            }
             Instructions::And {
                 destination,
                 source1,
@@ -290,9 +612,9 @@ impl InstructionsTrait for Instructions {
                 condition_flag,
             } => {
                 if condition_flag & registers.get(RegistersEnum::Condition) > 0 {
-                    let (result, _) = registers
+                    let result = registers
                         .get(RegistersEnum::ProgramCounter)
-                        .overflowing_add(*pc_offset);
+                        .wrapping_add(*pc_offset);
                     registers.set(RegistersEnum::ProgramCounter, result);
                 }
             }
@@ -306,9 +628,9 @@ impl InstructionsTrait for Instructions {
                 );
                 match jump_type {
                     JumpType::Long(pc_offset) => {
-                        let (result, _) = registers
+                        let result = registers
                             .get(RegistersEnum::ProgramCounter)
-                            .overflowing_add(*pc_offset);
+                            .wrapping_add(*pc_offset);
                         registers.set(RegistersEnum::ProgramCounter, result)
                     }
                     JumpType::Register(register) => {
@@ -320,9 +642,9 @@ impl InstructionsTrait for Instructions {
                 destination,
                 pc_offset,
             } => {
-                let (address, _) = registers
+                let address = registers
                     .get(RegistersEnum::ProgramCounter)
-                    .overflowing_add(*pc_offset);
+                    .wrapping_add(*pc_offset);
                 registers.set(*destination, memory.read(address, input));
                 registers.update_flags(*destination);
             }
@@ -331,7 +653,7 @@ impl InstructionsTrait for Instructions {
                 source1,
                 offset,
             } => {
-                let (address, _) = registers.get(*source1).overflowing_add(*offset);
+                let address = registers.get(*source1).wrapping_add(*offset);
                 registers.set(*destination, memory.read(address, input));
                 registers.update_flags(*destination);
             }
@@ -339,22 +661,22 @@ impl InstructionsTrait for Instructions {
                 destination,
                 pc_offset,
             } => {
-                let (result, _) = registers
+                let result = registers
                     .get(RegistersEnum::ProgramCounter)
-                    .overflowing_add(*pc_offset);
+                    .wrapping_add(*pc_offset);
                 registers.set(*destination, result);
                 registers.update_flags(*destination);
             }
             Instructions::Store { source, pc_offset } => {
-                let (address, _) = registers
+                let address = registers
                     .get(RegistersEnum::ProgramCounter)
-                    .overflowing_add(*pc_offset);
+                    .wrapping_add(*pc_offset);
                 memory.write(address, registers.get(*source));
             }
             Instructions::StoreIndirect { source, pc_offset } => {
-                let (address, _) = registers
+                let address = registers
                     .get(RegistersEnum::ProgramCounter)
-                    .overflowing_add(*pc_offset);
+                    .wrapping_add(*pc_offset);
                 let address = memory.read(address, input);
                 memory.write(address, registers.get(*source));
             }
@@ -363,14 +685,13 @@ impl InstructionsTrait for Instructions {
                 source2,
                 offset,
             } => {
-                let (address, _) = registers.get(*source2).overflowing_add(*offset);
+                let address = registers.get(*source2).wrapping_add(*offset);
                 memory.write(address, registers.get(*source1));
             }
             Instructions::Trap(routine) => match routine {
                 TrapRoutine::GETC => {
-                    let mut buffer = [0; 1];
-                    input.read_exact(&mut buffer)?;
-                    registers.set(RegistersEnum::R0, buffer[0] as u16);
+                    let key = read_key(memory, input)?;
+                    registers.set(RegistersEnum::R0, key);
                 }
                 TrapRoutine::OUT => {
                     let character = registers.get(RegistersEnum::R0) as u8 as char;
@@ -382,20 +703,15 @@ impl InstructionsTrait for Instructions {
                     while byte != 0x0000 {
                         let character = byte as u8 as char;
                         write!(output, "{character}")?;
-                        address += 1;
+                        address = address.wrapping_add(1);
                         byte = memory.read(address, input);
                     }
                     output.flush()?;
                 }
                 TrapRoutine::IN => {
                     output.flush()?;
-                    let character = input
-                        .bytes()
-                        .next()
-                        .and_then(|result| result.ok())
-                        .map(|byte| byte as u16)
-                        .unwrap();
-                    registers.set(RegistersEnum::R0, character);
+                    let key = read_key(memory, input)?;
+                    registers.set(RegistersEnum::R0, key);
                 }
                 TrapRoutine::PUTSP => {
                     let mut address = registers.get(RegistersEnum::R0);
@@ -405,19 +721,20 @@ impl InstructionsTrait for Instructions {
                         write!(output, "{character}")?;
                         let character = (byte >> 8) as u8 as char;
                         write!(output, "{character}")?;
-                        address += 1;
+                        address = address.wrapping_add(1);
                         byte = memory.read(address, input);
                     }
                     output.flush()?;
                 }
                 TrapRoutine::HALT => {
                     output.flush()?;
-                    process::exit(1)
+                    return Ok(ExecutionState::Halted);
                 }
+                TrapRoutine::Custom(vector) => interrupt::trap(registers, memory, input, *vector),
             },
         }
 
-        Ok(())
+        Ok(ExecutionState::Running)
     }
 }
 
@@ -425,13 +742,20 @@ impl InstructionsTrait for Instructions {
 mod test {
     use std::io::Cursor;
 
+    use proptest::prelude::*;
+
     use crate::{
         lc3::{
+            error::Error,
             instructions::{JumpType, TrapRoutine},
-            memory::Memory,
-            registers::{Registers, RegistersEnum, PROGRAM_START},
+            memory::{Memory, MemoryMappedReg},
+            registers::{Registers, RegistersEnum, PROGRAM_START, PSR_PRIVILEGE},
+        },
+        vm::{
+            instructions::{ExecutionState, InstructionsTrait},
+            memory::MemoryTrait,
+            registers::RegistersTrait,
         },
-        vm::{instructions::InstructionsTrait, memory::MemoryTrait, registers::RegistersTrait},
     };
 
     use super::{Instructions, RegisterMode};
@@ -952,4 +1276,328 @@ mod test {
         assert_eq!(output.get_ref()[0] as char, 'V');
         assert_eq!(output.get_ref()[1] as char, 'M');
     }
+
+    #[test]
+    fn test_trap_halt() {
+        let mut registers = Registers::default();
+        let mut memory = Memory::default();
+
+        let instruction = Instructions::Trap(TrapRoutine::HALT);
+        let state = instruction
+            .execute(
+                &mut registers,
+                &mut memory,
+                &mut std::io::stdin(),
+                &mut std::io::stdout(),
+            )
+            .unwrap();
+        assert_eq!(ExecutionState::Halted, state);
+    }
+
+    #[test]
+    fn test_rti() {
+        let mut registers = Registers::default();
+        let mut memory = Memory::default();
+
+        registers.set(RegistersEnum::ProcessorStatus, 0x0401);
+        registers.set(RegistersEnum::R6, 0x2FFE);
+        registers.set(RegistersEnum::SavedUserStackPointer, 0xFE00);
+        memory.write(0x2FFE, 0x3010);
+        memory.write(0x2FFF, PSR_PRIVILEGE | 0x0002);
+
+        let instruction = Instructions::RTI;
+        instruction
+            .execute(
+                &mut registers,
+                &mut memory,
+                &mut std::io::stdin(),
+                &mut std::io::stdout(),
+            )
+            .unwrap();
+        assert_eq!(0x3010, registers.get(RegistersEnum::ProgramCounter));
+        assert_eq!(
+            PSR_PRIVILEGE | 0x0002,
+            registers.get(RegistersEnum::ProcessorStatus)
+        );
+        assert_eq!(0xFE00, registers.get(RegistersEnum::R6));
+        assert_eq!(
+            0x3000,
+            registers.get(RegistersEnum::SavedSupervisorStackPointer)
+        );
+    }
+
+    #[test]
+    fn test_rti_privilege_mode_violation() {
+        let mut registers = Registers::default();
+        let mut memory = Memory::default();
+
+        let handler = 0x1000;
+        memory.write(0x0100, handler);
+        registers.set(RegistersEnum::R6, 0xFE00);
+        registers.set(RegistersEnum::ProgramCounter, PROGRAM_START + 1);
+
+        let instruction = Instructions::RTI;
+        instruction
+            .execute(
+                &mut registers,
+                &mut memory,
+                &mut std::io::stdin(),
+                &mut std::io::stdout(),
+            )
+            .unwrap();
+        assert_eq!(handler, registers.get(RegistersEnum::ProgramCounter));
+        assert_eq!(
+            0,
+            registers.get(RegistersEnum::ProcessorStatus) & PSR_PRIVILEGE
+        );
+        assert_eq!(0xFE00, registers.get(RegistersEnum::SavedUserStackPointer));
+        assert_eq!(0x2FFE, registers.get(RegistersEnum::R6));
+        assert_eq!(PROGRAM_START, memory.read(0x2FFE, &mut std::io::stdin()));
+        assert_eq!(PSR_PRIVILEGE, memory.read(0x2FFF, &mut std::io::stdin()));
+    }
+
+    #[test]
+    fn test_res_illegal_opcode() {
+        let mut registers = Registers::default();
+        let mut memory = Memory::default();
+
+        let handler = 0x1100;
+        memory.write(0x0101, handler);
+
+        let instruction = Instructions::RES;
+        instruction
+            .execute(
+                &mut registers,
+                &mut memory,
+                &mut std::io::stdin(),
+                &mut std::io::stdout(),
+            )
+            .unwrap();
+        assert_eq!(handler, registers.get(RegistersEnum::ProgramCounter));
+    }
+
+    #[test]
+    fn test_trap_custom() {
+        let mut registers = Registers::default();
+        let mut memory = Memory::default();
+
+        let routine = 0x0400;
+        memory.write(0x0026, routine);
+        registers.set(RegistersEnum::R6, 0xFE00);
+
+        let instruction = Instructions::Trap(TrapRoutine::Custom(0x26));
+        instruction
+            .execute(
+                &mut registers,
+                &mut memory,
+                &mut std::io::stdin(),
+                &mut std::io::stdout(),
+            )
+            .unwrap();
+        assert_eq!(routine, registers.get(RegistersEnum::ProgramCounter));
+        assert_eq!(PROGRAM_START, registers.get(RegistersEnum::R7));
+        assert_eq!(
+            0,
+            registers.get(RegistersEnum::ProcessorStatus) & PSR_PRIVILEGE
+        );
+        assert_eq!(0x2FFE, registers.get(RegistersEnum::R6));
+        assert_eq!(PROGRAM_START, memory.read(0x2FFE, &mut std::io::stdin()));
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        for word in 0..=u16::MAX {
+            let instruction = Instructions::try_from(word).unwrap();
+            let encoded = instruction.encode().unwrap();
+            assert_eq!(instruction, Instructions::try_from(encoded).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_encode_invalid() {
+        let instruction = Instructions::Add {
+            destination: RegistersEnum::R0,
+            source1: RegistersEnum::R1,
+            source2: RegisterMode::Immediate(16),
+        };
+        assert!(matches!(
+            instruction.encode(),
+            Err(Error::OutOfRange { value: 16, bits: 5 })
+        ));
+
+        let instruction = Instructions::Jump {
+            source: RegistersEnum::ProgramCounter,
+        };
+        assert!(matches!(
+            instruction.encode(),
+            Err(Error::UnknownRegister(8))
+        ));
+
+        let instruction = Instructions::Branch {
+            pc_offset: 0,
+            condition_flag: 0x8,
+        };
+        assert!(instruction.encode().is_err());
+    }
+
+    fn edge_address() -> impl Strategy<Value = u16> {
+        prop_oneof![0xFF00..=0xFFFFu16, 0x0000..=0x00FFu16, any::<u16>()]
+    }
+
+    // Addresses backed by plain memory rather than a memory mapped register.
+    fn is_plain(address: u16) -> bool {
+        ![
+            MemoryMappedReg::Kbsr as u16,
+            MemoryMappedReg::Kbdr as u16,
+            MemoryMappedReg::Dsr as u16,
+            MemoryMappedReg::Ddr as u16,
+            MemoryMappedReg::Mcr as u16,
+        ]
+        .contains(&address)
+    }
+
+    fn offset(field: u16, bits: u32) -> u16 {
+        (((field << (16 - bits)) as i16) >> (16 - bits)) as u16
+    }
+
+    fn execute(word: u16, registers: &mut Registers, memory: &mut Memory) -> Vec<u8> {
+        let mut output = Vec::new();
+        let instruction: Instructions = word.try_into().unwrap();
+        instruction
+            .execute(registers, memory, &mut Cursor::new([]), &mut output)
+            .unwrap();
+        output
+    }
+
+    proptest! {
+        #[test]
+        fn test_encode_offsets(pc_offset: u16, offset: u16) {
+            let instruction = Instructions::LoadEffectiveAddress {
+                destination: RegistersEnum::R3,
+                pc_offset,
+            };
+            let fits = (-256..256).contains(&(pc_offset as i16));
+            match instruction.encode() {
+                Ok(word) => {
+                    prop_assert!(fits);
+                    prop_assert_eq!(instruction, Instructions::try_from(word).unwrap());
+                }
+                Err(_) => prop_assert!(!fits),
+            }
+
+            let instruction = Instructions::StoreRegister {
+                source1: RegistersEnum::R1,
+                source2: RegistersEnum::R6,
+                offset,
+            };
+            let fits = (-32..32).contains(&(offset as i16));
+            match instruction.encode() {
+                Ok(word) => {
+                    prop_assert!(fits);
+                    prop_assert_eq!(instruction, Instructions::try_from(word).unwrap());
+                }
+                Err(_) => prop_assert!(!fits),
+            }
+        }
+
+        #[test]
+        fn test_wrapping_arithmetic(a in edge_address(), b in edge_address(), imm5 in 0..0x20u16) {
+            let mut registers = Registers::default();
+            let mut memory = Memory::default();
+            registers.set(RegistersEnum::R1, a);
+            registers.set(RegistersEnum::R2, b);
+
+            execute(0x1042, &mut registers, &mut memory);
+            prop_assert_eq!(a.wrapping_add(b), registers.get(RegistersEnum::R0));
+            execute(0x1060 | imm5, &mut registers, &mut memory);
+            prop_assert_eq!(a.wrapping_add(offset(imm5, 5)), registers.get(RegistersEnum::R0));
+            execute(0x5042, &mut registers, &mut memory);
+            prop_assert_eq!(a & b, registers.get(RegistersEnum::R0));
+            execute(0x907F, &mut registers, &mut memory);
+            prop_assert_eq!(!a, registers.get(RegistersEnum::R0));
+        }
+
+        #[test]
+        fn test_wrapping_control_flow(pc in edge_address(), target in edge_address(), field in 0..0x800u16) {
+            let mut registers = Registers::default();
+            let mut memory = Memory::default();
+            registers.set(RegistersEnum::R1, target);
+            registers.update_flags(RegistersEnum::R1);
+
+            registers.set_pc(pc);
+            execute(0x0E00 | (field & 0x1FF), &mut registers, &mut memory);
+            prop_assert_eq!(pc.wrapping_add(offset(field & 0x1FF, 9)), registers.get_pc());
+
+            registers.set_pc(pc);
+            execute(0x4800 | field, &mut registers, &mut memory);
+            prop_assert_eq!(pc.wrapping_add(offset(field, 11)), registers.get_pc());
+            prop_assert_eq!(pc, registers.get(RegistersEnum::R7));
+
+            registers.set_pc(pc);
+            execute(0x4040, &mut registers, &mut memory);
+            prop_assert_eq!(target, registers.get_pc());
+
+            execute(0xC040, &mut registers, &mut memory);
+            prop_assert_eq!(target, registers.get_pc());
+        }
+
+        #[test]
+        fn test_wrapping_pc_relative(pc in edge_address(), field in 0..0x200u16, pointer in edge_address(), value: u16) {
+            let address = pc.wrapping_add(offset(field, 9));
+            prop_assume!(is_plain(address) && is_plain(pointer) && address != pointer);
+            let mut registers = Registers::default();
+            let mut memory = Memory::default();
+            registers.set_pc(pc);
+
+            execute(0xE000 | field, &mut registers, &mut memory);
+            prop_assert_eq!(address, registers.get(RegistersEnum::R0));
+
+            registers.set(RegistersEnum::R1, value);
+            execute(0x3200 | field, &mut registers, &mut memory);
+            execute(0x2400 | field, &mut registers, &mut memory);
+            prop_assert_eq!(value, registers.get(RegistersEnum::R2));
+
+            memory.write(address, pointer);
+            execute(0xB200 | field, &mut registers, &mut memory);
+            prop_assert_eq!(value, memory.read(pointer, &mut Cursor::new([])));
+            execute(0xA600 | field, &mut registers, &mut memory);
+            prop_assert_eq!(value, registers.get(RegistersEnum::R3));
+        }
+
+        #[test]
+        fn test_wrapping_base_offset(base in edge_address(), field in 0..0x40u16, value: u16) {
+            let address = base.wrapping_add(offset(field, 6));
+            prop_assume!(is_plain(address));
+            let mut registers = Registers::default();
+            let mut memory = Memory::default();
+            registers.set(RegistersEnum::R1, base);
+            registers.set(RegistersEnum::R2, value);
+
+            execute(0x7440 | field, &mut registers, &mut memory);
+            prop_assert_eq!(value, memory.read(address, &mut Cursor::new([])));
+            execute(0x6640 | field, &mut registers, &mut memory);
+            prop_assert_eq!(value, registers.get(RegistersEnum::R3));
+        }
+
+        #[test]
+        fn test_wrapping_strings(start in 0xFFF0..=0xFFFFu16) {
+            let mut registers = Registers::default();
+            let mut memory = Memory::default();
+            let mut address = start;
+            for character in b"wrap" {
+                memory.write(address, *character as u16);
+                address = address.wrapping_add(1);
+            }
+            memory.write(address, 0);
+            registers.set(RegistersEnum::R0, start);
+            prop_assume!((0..5).all(|index| is_plain(start.wrapping_add(index))));
+
+            prop_assert_eq!(b"wrap".to_vec(), execute(0xF022, &mut registers, &mut memory));
+
+            memory.write(start, u16::from_le_bytes(*b"wr"));
+            memory.write(start.wrapping_add(1), u16::from_le_bytes(*b"ap"));
+            memory.write(start.wrapping_add(2), 0);
+            prop_assert_eq!(b"wrap".to_vec(), execute(0xF024, &mut registers, &mut memory));
+        }
+    }
 }
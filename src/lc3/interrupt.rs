@@ -0,0 +1,193 @@
+use std::io::Read;
+
+use crate::vm::{memory::MemoryTrait, registers::RegistersTrait};
+
+use super::registers::{RegistersEnum, PSR_PRIORITY, PSR_PRIVILEGE};
+
+pub const TRAP_VECTOR_TABLE: u16 = 0x0000;
+pub const INTERRUPT_VECTOR_TABLE: u16 = 0x0100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Exception {
+    PrivilegeModeViolation = 0x00,
+    IllegalOpcode = 0x01,
+}
+
+/// Switches to supervisor mode, pushes PSR and `return_address` on the
+/// supervisor stack and loads the PC from the interrupt vector table.
+/// A `priority` is only given for interrupts; exceptions keep the current one.
+pub fn initiate<R, M, I>(
+    registers: &mut R,
+    memory: &mut M,
+    input: &mut I,
+    vector: u8,
+    priority: Option<u16>,
+    return_address: u16,
+) where
+    R: RegistersTrait<ValueType = u16, RegisterSet = RegistersEnum>,
+    M: MemoryTrait<ValueType = u16>,
+    I: Read,
+{
+    enter_supervisor(registers, memory, priority, return_address);
+    let handler = memory.read(INTERRUPT_VECTOR_TABLE.wrapping_add(vector as u16), input);
+    registers.set_pc(handler);
+}
+
+/// Performs TRAP the way the hardware does: the return address goes to R7
+/// and on the supervisor stack, and the PC is loaded from the trap vector table.
+pub fn trap<R, M, I>(registers: &mut R, memory: &mut M, input: &mut I, vector: u8)
+where
+    R: RegistersTrait<ValueType = u16, RegisterSet = RegistersEnum>,
+    M: MemoryTrait<ValueType = u16>,
+    I: Read,
+{
+    let return_address = registers.get_pc();
+    registers.set(RegistersEnum::R7, return_address);
+    enter_supervisor(registers, memory, None, return_address);
+    let handler = memory.read(TRAP_VECTOR_TABLE.wrapping_add(vector as u16), input);
+    registers.set_pc(handler);
+}
+
+/// Pops PC and PSR from the supervisor stack, switching back to the user
+/// stack if the restored PSR is in user mode. Callers check the privilege.
+pub fn restore<R, M, I>(registers: &mut R, memory: &mut M, input: &mut I)
+where
+    R: RegistersTrait<ValueType = u16, RegisterSet = RegistersEnum>,
+    M: MemoryTrait<ValueType = u16>,
+    I: Read,
+{
+    let pc = pop(registers, memory, input);
+    let psr = pop(registers, memory, input);
+    registers.set_pc(pc);
+    registers.set(RegistersEnum::ProcessorStatus, psr);
+
+    if psr & PSR_PRIVILEGE != 0 {
+        registers.set(
+            RegistersEnum::SavedSupervisorStackPointer,
+            registers.get(RegistersEnum::R6),
+        );
+        registers.set(
+            RegistersEnum::R6,
+            registers.get(RegistersEnum::SavedUserStackPointer),
+        );
+    }
+}
+
+fn enter_supervisor<R, M>(
+    registers: &mut R,
+    memory: &mut M,
+    priority: Option<u16>,
+    return_address: u16,
+) where
+    R: RegistersTrait<ValueType = u16, RegisterSet = RegistersEnum>,
+    M: MemoryTrait<ValueType = u16>,
+{
+    let psr = registers.get(RegistersEnum::ProcessorStatus);
+    if psr & PSR_PRIVILEGE != 0 {
+        registers.set(
+            RegistersEnum::SavedUserStackPointer,
+            registers.get(RegistersEnum::R6),
+        );
+        registers.set(
+            RegistersEnum::R6,
+            registers.get(RegistersEnum::SavedSupervisorStackPointer),
+        );
+    }
+    push(registers, memory, psr);
+    push(registers, memory, return_address);
+
+    let mut psr = psr & !PSR_PRIVILEGE;
+    if let Some(priority) = priority {
+        psr = (psr & !PSR_PRIORITY) | ((priority << 8) & PSR_PRIORITY);
+    }
+    registers.set(RegistersEnum::ProcessorStatus, psr);
+}
+
+fn push<R, M>(registers: &mut R, memory: &mut M, value: u16)
+where
+    R: RegistersTrait<ValueType = u16, RegisterSet = RegistersEnum>,
+    M: MemoryTrait<ValueType = u16>,
+{
+    let stack_pointer = registers.get(RegistersEnum::R6).wrapping_sub(1);
+    registers.set(RegistersEnum::R6, stack_pointer);
+    memory.write(stack_pointer, value);
+}
+
+fn pop<R, M, I>(registers: &mut R, memory: &mut M, input: &mut I) -> u16
+where
+    R: RegistersTrait<ValueType = u16, RegisterSet = RegistersEnum>,
+    M: MemoryTrait<ValueType = u16>,
+    I: Read,
+{
+    let stack_pointer = registers.get(RegistersEnum::R6);
+    registers.set(RegistersEnum::R6, stack_pointer.wrapping_add(1));
+    memory.read(stack_pointer, input)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Interrupt {
+    pub priority: u16,
+    pub vector: u8,
+}
+
+pub const KEYBOARD_INTERRUPT: Interrupt = Interrupt {
+    priority: 4,
+    vector: 0x80,
+};
+
+/// Collects the interrupt requests devices assert during a cycle. Requests
+/// are level triggered: devices raise them again every cycle they are active.
+#[derive(Debug, Default)]
+pub struct InterruptController {
+    requests: Vec<Interrupt>,
+}
+
+impl InterruptController {
+    pub fn raise(&mut self, interrupt: Interrupt) {
+        if !self.requests.contains(&interrupt) {
+            self.requests.push(interrupt);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.requests.clear();
+    }
+
+    /// Returns the highest priority request above `priority`, if any.
+    pub fn take(&mut self, priority: u16) -> Option<Interrupt> {
+        let index = self
+            .requests
+            .iter()
+            .enumerate()
+            .filter(|(_, interrupt)| interrupt.priority > priority)
+            .max_by_key(|(_, interrupt)| interrupt.priority)
+            .map(|(index, _)| index)?;
+        Some(self.requests.remove(index))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Interrupt, InterruptController};
+
+    #[test]
+    fn test_take_highest_priority() {
+        let mut controller = InterruptController::default();
+        let low = Interrupt {
+            priority: 2,
+            vector: 0x81,
+        };
+        let high = Interrupt {
+            priority: 6,
+            vector: 0x82,
+        };
+        controller.raise(low);
+        controller.raise(high);
+
+        assert_eq!(None, controller.take(7));
+        assert_eq!(Some(high), controller.take(4));
+        assert_eq!(None, controller.take(4));
+        assert_eq!(Some(low), controller.take(0));
+        assert_eq!(None, controller.take(0));
+    }
+}
@@ -0,0 +1,268 @@
+use std::{
+    collections::VecDeque,
+    io::{ErrorKind, Read},
+};
+
+use super::{
+    device::Device,
+    error::Error,
+    interrupt::{Interrupt, KEYBOARD_INTERRUPT},
+    memory::MemoryMappedReg,
+    snapshot::Decoder,
+};
+
+pub const KBSR_READY: u16 = 1 << 15;
+const KBSR_INTERRUPT_ENABLE: u16 = 1 << 14;
+
+/// What the keyboard does once its input is exhausted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EofPolicy {
+    /// Report a key with this value, e.g. 0xFFFF or 0x0004 (EOT), forever.
+    Value(u16),
+    /// Halt the machine.
+    Halt,
+    /// Stop execution with [`Error::InputExhausted`](super::error::Error::InputExhausted).
+    #[default]
+    Error,
+}
+
+/// Keyboard behind KBSR/KBDR. Keys are queued in an internal buffer and
+/// KBSR reports ready while one is queued; reading KBDR consumes it. The
+/// input is polled without blocking whenever the buffer is empty and KBSR is
+/// read, or between instructions while KBSR[14] enables interrupts. Inputs
+/// that would block report [`ErrorKind::WouldBlock`]; an input that fails
+/// is treated as exhausted.
+#[derive(Debug, Default)]
+pub struct Keyboard {
+    buffer: VecDeque<u8>,
+    interrupt_enable: bool,
+    data: u16,
+    eof_policy: EofPolicy,
+    exhausted: bool,
+}
+
+impl Keyboard {
+    pub fn push_key(&mut self, key: u8) {
+        self.buffer.push_back(key);
+    }
+
+    pub fn eof_policy(&self) -> EofPolicy {
+        self.eof_policy
+    }
+
+    pub fn set_eof_policy(&mut self, eof_policy: EofPolicy) {
+        self.eof_policy = eof_policy;
+    }
+
+    /// Whether the input ran out while no keys were left in the buffer.
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted && self.buffer.is_empty()
+    }
+
+    pub fn is_ready(&self) -> bool {
+        !self.buffer.is_empty() || self.eof_value().is_some()
+    }
+
+    fn eof_value(&self) -> Option<u16> {
+        match self.eof_policy {
+            EofPolicy::Value(value) if self.exhausted => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn status(&self) -> u16 {
+        let mut status = 0;
+        if self.is_ready() {
+            status |= KBSR_READY;
+        }
+        if self.interrupt_enable {
+            status |= KBSR_INTERRUPT_ENABLE;
+        }
+        status
+    }
+
+    fn poll(&mut self, input: &mut dyn Read) {
+        if self.is_ready() {
+            return;
+        }
+
+        let mut buffer = [0u8; 1];
+        loop {
+            match input.read(&mut buffer) {
+                Ok(0) => {
+                    self.exhausted = true;
+                    break;
+                }
+                Ok(_) => {
+                    self.push_key(buffer[0]);
+                    break;
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    // a failed input ends it, unless the read only gave up
+                    // because the machine was paused
+                    if !matches!(Error::from(e), Error::Paused) {
+                        self.exhausted = true;
+                    }
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl Device for Keyboard {
+    fn read(&mut self, address: u16, input: &mut dyn Read) -> u16 {
+        if address == MemoryMappedReg::Kbsr as u16 {
+            self.poll(input);
+            self.status()
+        } else {
+            if let Some(key) = self.buffer.pop_front() {
+                self.data = key as u16;
+            } else if let Some(value) = self.eof_value() {
+                self.data = value;
+            }
+            self.data
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u16) {
+        if address == MemoryMappedReg::Kbsr as u16 {
+            self.interrupt_enable = value & KBSR_INTERRUPT_ENABLE != 0;
+        }
+    }
+
+    fn peek(&self, address: u16) -> u16 {
+        if address == MemoryMappedReg::Kbsr as u16 {
+            self.status()
+        } else {
+            self.data
+        }
+    }
+
+    fn tick(&mut self, input: &mut dyn Read) {
+        if self.interrupt_enable {
+            self.poll(input);
+        }
+    }
+
+    fn interrupt(&self) -> Option<Interrupt> {
+        (self.interrupt_enable && self.is_ready()).then_some(KEYBOARD_INTERRUPT)
+    }
+
+    fn save(&self) -> Vec<u8> {
+        let flags = self.interrupt_enable as u8 | (self.exhausted as u8) << 1;
+        let mut state = vec![flags];
+        state.extend(self.data.to_be_bytes());
+        state.extend((self.buffer.len() as u32).to_be_bytes());
+        state.extend(&self.buffer);
+        state
+    }
+
+    fn load(&mut self, state: &[u8]) -> Result<(), Error> {
+        let mut state = Decoder::new(state);
+        let flags = state.u8()?;
+        self.interrupt_enable = flags & 1 != 0;
+        self.exhausted = flags & 2 != 0;
+        self.data = state.u16()?;
+        let count = state.u32()? as usize;
+        self.buffer = state.bytes(count)?.iter().copied().collect();
+        state.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, ErrorKind, Read};
+
+    use crate::lc3::{device::Device, error::Error, memory::MemoryMappedReg};
+
+    use super::{EofPolicy, Keyboard, KBSR_READY};
+
+    struct WouldBlock;
+
+    impl Read for WouldBlock {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(ErrorKind::WouldBlock.into())
+        }
+    }
+
+    struct Failing;
+
+    impl Read for Failing {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(ErrorKind::BrokenPipe.into())
+        }
+    }
+
+    struct Paused;
+
+    impl Read for Paused {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other(Error::Paused))
+        }
+    }
+
+    const KBSR: u16 = MemoryMappedReg::Kbsr as u16;
+    const KBDR: u16 = MemoryMappedReg::Kbdr as u16;
+
+    #[test]
+    fn test_ready_bit() {
+        let mut keyboard = Keyboard::default();
+        let mut input = Cursor::new(b"ab".to_vec());
+
+        assert_eq!(KBSR_READY, keyboard.read(KBSR, &mut input));
+        assert_eq!(KBSR_READY, keyboard.read(KBSR, &mut input));
+        assert_eq!('a' as u16, keyboard.read(KBDR, &mut input));
+        assert!(!keyboard.is_ready());
+        assert_eq!(KBSR_READY, keyboard.read(KBSR, &mut input));
+        assert_eq!('b' as u16, keyboard.read(KBDR, &mut input));
+        assert_eq!(0, keyboard.read(KBSR, &mut input));
+    }
+
+    #[test]
+    fn test_would_block() {
+        let mut keyboard = Keyboard::default();
+
+        assert_eq!(0, keyboard.read(KBSR, &mut WouldBlock));
+        keyboard.push_key(b'q');
+        assert_eq!(KBSR_READY, keyboard.read(KBSR, &mut WouldBlock));
+        assert_eq!('q' as u16, keyboard.read(KBDR, &mut WouldBlock));
+        assert_eq!(0, keyboard.read(KBSR, &mut WouldBlock));
+    }
+
+    #[test]
+    fn test_eof() {
+        let mut keyboard = Keyboard::default();
+        let mut input = Cursor::new(b"a".to_vec());
+
+        assert_eq!(KBSR_READY, keyboard.read(KBSR, &mut input));
+        assert!(!keyboard.is_exhausted());
+        assert_eq!('a' as u16, keyboard.read(KBDR, &mut input));
+        assert_eq!(0, keyboard.read(KBSR, &mut input));
+        assert!(keyboard.is_exhausted());
+
+        keyboard.set_eof_policy(EofPolicy::Value(0x0004));
+        assert_eq!(KBSR_READY, keyboard.read(KBSR, &mut input));
+        assert_eq!(0x0004, keyboard.read(KBDR, &mut input));
+        assert_eq!(KBSR_READY, keyboard.read(KBSR, &mut input));
+    }
+
+    #[test]
+    fn test_failing_input() {
+        let mut keyboard = Keyboard::default();
+
+        assert_eq!(0, keyboard.read(KBSR, &mut Failing));
+        assert!(keyboard.is_exhausted());
+
+        keyboard.set_eof_policy(EofPolicy::Value(0xFFFF));
+        assert_eq!(KBSR_READY, keyboard.read(KBSR, &mut Failing));
+        assert_eq!(0xFFFF, keyboard.read(KBDR, &mut Failing));
+
+        // pausing the machine does not end the input
+        let mut keyboard = Keyboard::default();
+        assert_eq!(0, keyboard.read(KBSR, &mut Paused));
+        assert!(!keyboard.is_exhausted());
+    }
+}
@@ -1,22 +1,273 @@
-use std::io::Read;
+use std::{
+    cell::Cell,
+    io::{ErrorKind, Read, Write},
+    ops::RangeInclusive,
+    rc::Rc,
+};
 
-use crate::vm::{instructions::InstructionsTrait, memory::MemoryTrait, registers::RegistersTrait};
+use crate::vm::{
+    instructions::{ExecutionState, InstructionsTrait},
+    memory::MemoryTrait,
+    registers::RegistersTrait,
+};
 
 use super::{
+    console::{Hotkey, Shared},
+    device::Device,
+    disasm::{self, Line},
     error::Error,
+    history::{History, Undo},
     instructions::Instructions,
-    memory::Memory,
+    interrupt::{self, Interrupt, InterruptController},
+    keyboard::EofPolicy,
+    memory::{Access, Memory},
+    os::OS_IMAGE,
+    profiler::Profiler,
     registers::{Registers, RegistersEnum},
+    replay::{Recorder, Recording, Replayer},
+    snapshot::Snapshot,
+    stats::Stats,
+    trace::Tracer,
 };
 // use crate::vm::machine::VirtualMachine;
 
-#[derive(Default)]
 pub struct LittleComputer3 {
     memory: Memory,
     registers: Registers,
+    cycles: u64,
+    /// The cycle count for input recording and replay.
+    clock: Rc<Cell<u64>>,
+    interrupts: InterruptController,
+    trap_mode: TrapMode,
+    input: Box<dyn Read>,
+    output: Box<dyn Write>,
+    tracer: Option<Tracer>,
+    history: History,
+    stats: Option<Stats>,
+    profiler: Option<Profiler>,
+    /// Records memory accesses even if nothing attached needs them.
+    record_accesses: bool,
+    pause: Option<Hotkey>,
+}
+
+impl Default for LittleComputer3 {
+    fn default() -> Self {
+        Self {
+            memory: Memory::default(),
+            registers: Registers::default(),
+            cycles: 0,
+            clock: Rc::default(),
+            interrupts: InterruptController::default(),
+            trap_mode: TrapMode::default(),
+            input: Box::new(std::io::stdin()),
+            output: Box::new(std::io::stdout()),
+            tracer: None,
+            history: History::default(),
+            stats: None,
+            profiler: None,
+            record_accesses: false,
+            pause: None,
+        }
+    }
+}
+
+/// The registers in a [`Snapshot`], in order.
+const SNAPSHOT_REGISTERS: [RegistersEnum; 12] = [
+    RegistersEnum::R0,
+    RegistersEnum::R1,
+    RegistersEnum::R2,
+    RegistersEnum::R3,
+    RegistersEnum::R4,
+    RegistersEnum::R5,
+    RegistersEnum::R6,
+    RegistersEnum::R7,
+    RegistersEnum::ProgramCounter,
+    RegistersEnum::ProcessorStatus,
+    RegistersEnum::SavedUserStackPointer,
+    RegistersEnum::SavedSupervisorStackPointer,
+];
+
+/// How TRAP instructions are executed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TrapMode {
+    /// The standard service routines run natively in Rust, any other vector
+    /// goes through the trap vector table.
+    #[default]
+    Native,
+    /// Every TRAP goes through the trap vector table, like on the hardware.
+    Vectored,
+}
+
+/// Result of executing a single instruction with [`LittleComputer3::step`].
+#[derive(Debug)]
+pub struct StepOutcome {
+    pub interrupt: Option<Interrupt>,
+    pub instruction: Instructions,
+    pub pc_before: u16,
+    pub pc_after: u16,
+    pub halted: bool,
+    /// Every memory access of the step in order, including the fetch and those
+    /// made by native TRAP routines and by entering an interrupt. Only recorded
+    /// with [`LittleComputer3::set_record_accesses`] or while a tracer, undo
+    /// history, profiler or stats are attached, empty otherwise.
+    pub accesses: Vec<Access>,
+}
+
+/// Reason a run loop returned control to the caller.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RunOutcome {
+    Halted { pc: u16, cycles: u64 },
+    Paused { pc: u16, cycles: u64 },
+}
+
+/// The input of native TRAP routines, which stop waiting for a key once the
+/// machine is paused.
+struct PausableInput<'a> {
+    input: &'a mut dyn Read,
+    pause: Option<&'a Hotkey>,
+}
+
+impl Read for PausableInput<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self.input.read(buf) {
+            Err(e)
+                if e.kind() == ErrorKind::WouldBlock
+                    && self.pause.is_some_and(Hotkey::is_pressed) =>
+            {
+                Err(std::io::Error::other(Error::Paused))
+            }
+            result => result,
+        }
+    }
+}
+
+/// Builds a [`LittleComputer3`] with additional devices and settings.
+#[derive(Default)]
+pub struct LittleComputer3Builder {
+    machine: LittleComputer3,
+    os: bool,
+}
+
+impl LittleComputer3Builder {
+    /// Attaches `device` to the addresses in `range`.
+    pub fn device<D>(mut self, range: RangeInclusive<u16>, device: D) -> Self
+    where
+        D: Device + 'static,
+    {
+        self.machine.attach_device(range, device);
+        self
+    }
+
+    /// Loads the bundled operating system and runs every TRAP through it.
+    pub fn os(mut self) -> Self {
+        self.os = true;
+        self
+    }
+
+    pub fn trap_mode(mut self, trap_mode: TrapMode) -> Self {
+        self.machine.set_trap_mode(trap_mode);
+        self
+    }
+
+    /// Reads keyboard input from `input` instead of stdin.
+    pub fn input<I>(mut self, input: I) -> Self
+    where
+        I: Read + 'static,
+    {
+        self.machine.set_input(input);
+        self
+    }
+
+    /// Writes console output to `output` instead of stdout.
+    pub fn output<O>(mut self, output: O) -> Self
+    where
+        O: Write + 'static,
+    {
+        self.machine.set_output(output);
+        self
+    }
+
+    /// Uses a single stream, such as a socket, for both input and output.
+    pub fn console<C>(mut self, console: C) -> Self
+    where
+        C: Read + Write + 'static,
+    {
+        self.machine.set_console(console);
+        self
+    }
+
+    /// Logs the input set so far to `log`, see
+    /// [`LittleComputer3::record_input`].
+    pub fn record_input<W>(mut self, log: W) -> Self
+    where
+        W: Write + 'static,
+    {
+        self.machine.record_input(log);
+        self
+    }
+
+    /// Reads keyboard input from `recording`, see
+    /// [`LittleComputer3::replay_input`].
+    pub fn replay_input(mut self, recording: Recording) -> Self {
+        self.machine.replay_input(recording);
+        self
+    }
+
+    pub fn eof_policy(mut self, eof_policy: EofPolicy) -> Self {
+        self.machine.set_eof_policy(eof_policy);
+        self
+    }
+
+    pub fn display_latency(mut self, latency: u32) -> Self {
+        self.machine.set_display_latency(latency);
+        self
+    }
+
+    /// Keeps an undo log of the last `capacity` instructions for
+    /// [`LittleComputer3::step_back`].
+    pub fn history(mut self, capacity: usize) -> Self {
+        self.machine.set_history_capacity(capacity);
+        self
+    }
+
+    /// Counts the work the machine does in `stats`.
+    pub fn stats(mut self, stats: Stats) -> Self {
+        self.machine.set_stats(Some(stats));
+        self
+    }
+
+    /// Attributes every executed instruction to its address and subroutine.
+    pub fn profiler(mut self, profiler: Profiler) -> Self {
+        self.machine.set_profiler(Some(profiler));
+        self
+    }
+
+    /// Records every executed instruction with `tracer`.
+    pub fn tracer(mut self, tracer: Tracer) -> Self {
+        self.machine.set_tracer(Some(tracer));
+        self
+    }
+
+    /// Pauses the run loops once `pause` is pressed, see
+    /// [`LittleComputer3::set_pause`].
+    pub fn pause(mut self, pause: Hotkey) -> Self {
+        self.machine.set_pause(Some(pause));
+        self
+    }
+
+    pub fn build(mut self) -> Result<LittleComputer3, Error> {
+        if self.os {
+            self.machine.load_os()?;
+        }
+        Ok(self.machine)
+    }
 }
 
 impl LittleComputer3 {
+    pub fn builder() -> LittleComputer3Builder {
+        LittleComputer3Builder::default()
+    }
+
     pub fn load_program(&mut self, mut source: impl Read) -> Result<(), Error> {
         let mut buffer = [0u8; 2];
 
@@ -26,7 +277,7 @@ impl LittleComputer3 {
             match source.read_exact(&mut buffer) {
                 Ok(_) => {
                     self.memory.write(address, u16::from_be_bytes(buffer));
-                    address += 1;
+                    address = address.wrapping_add(1);
                 }
                 Err(e) => {
                     if e.kind() == std::io::ErrorKind::UnexpectedEof {
@@ -41,34 +292,957 @@ impl LittleComputer3 {
         Ok(())
     }
 
-    pub fn execute_program(&mut self, debug: bool) -> Result<(), Error> {
-        let mut input = std::io::stdin();
-        let mut output = std::io::stdout();
+    /// Loads the bundled operating system and runs every TRAP through it.
+    pub fn load_os(&mut self) -> Result<(), Error> {
+        self.load_program(OS_IMAGE)?;
+        self.trap_mode = TrapMode::Vectored;
+        Ok(())
+    }
 
-        while self.registers.get(RegistersEnum::ProgramCounter) < u16::MAX {
-            let instruction: Instructions = self
-                .memory
-                .read(
-                    self.registers.get(RegistersEnum::ProgramCounter),
-                    &mut input,
-                )
-                .try_into()?;
-            self.registers.set(
-                RegistersEnum::ProgramCounter,
-                self.registers.get(RegistersEnum::ProgramCounter) + 1,
-            );
-            if debug {
-                println!(" => {instruction:?}");
-                println!(" => {:?}", self.registers);
-            }
-            instruction.execute(
+    pub fn trap_mode(&self) -> TrapMode {
+        self.trap_mode
+    }
+
+    pub fn set_trap_mode(&mut self, trap_mode: TrapMode) {
+        self.trap_mode = trap_mode;
+    }
+
+    pub fn eof_policy(&self) -> EofPolicy {
+        self.memory.bus().keyboard().eof_policy()
+    }
+
+    /// Decides what happens once the input is exhausted.
+    pub fn set_eof_policy(&mut self, eof_policy: EofPolicy) {
+        self.memory
+            .bus_mut()
+            .keyboard_mut()
+            .set_eof_policy(eof_policy);
+    }
+
+    /// Simulates a display that needs `latency` instructions per character.
+    pub fn set_display_latency(&mut self, latency: u32) {
+        self.memory.bus_mut().display_mut().set_latency(latency);
+    }
+
+    pub fn set_input<I>(&mut self, input: I)
+    where
+        I: Read + 'static,
+    {
+        self.input = Box::new(input);
+    }
+
+    pub fn set_output<O>(&mut self, output: O)
+    where
+        O: Write + 'static,
+    {
+        self.output = Box::new(output);
+    }
+
+    pub fn set_console<C>(&mut self, console: C)
+    where
+        C: Read + Write + 'static,
+    {
+        let console = Shared::new(console);
+        self.input = Box::new(console.clone());
+        self.output = Box::new(console);
+    }
+
+    /// Logs every byte read from the current input together with the cycle
+    /// it was read at, in the format of a [`Recording`].
+    pub fn record_input<W>(&mut self, log: W)
+    where
+        W: Write + 'static,
+    {
+        let input = std::mem::replace(&mut self.input, Box::new(std::io::empty()));
+        self.input = Box::new(Recorder::new(input, self.clock.clone(), Box::new(log)));
+    }
+
+    /// Replaces the input with `recording`, handing out each byte at the
+    /// cycle it was recorded at so the recorded run repeats exactly.
+    pub fn replay_input(&mut self, recording: Recording) {
+        self.input = Box::new(Replayer::new(recording, self.clock.clone()));
+    }
+
+    /// Starts a new undo log of `capacity` instructions, 0 disables it.
+    pub fn set_history_capacity(&mut self, capacity: usize) {
+        self.history = History::new(capacity);
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// What the machine executed since the stats were attached.
+    pub fn stats(&self) -> Option<&Stats> {
+        self.stats.as_ref()
+    }
+
+    pub fn set_stats(&mut self, stats: Option<Stats>) {
+        self.stats = stats;
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    pub fn set_profiler(&mut self, profiler: Option<Profiler>) {
+        self.profiler = profiler;
+    }
+
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) {
+        self.tracer = tracer;
+    }
+
+    /// Records the memory accesses of every step in
+    /// [`StepOutcome::accesses`], e.g. to check watchpoints.
+    pub fn set_record_accesses(&mut self, record: bool) {
+        self.record_accesses = record;
+    }
+
+    /// Once `pause` is pressed, the run loops return [`RunOutcome::Paused`]
+    /// before the next instruction. A native TRAP waiting for a key gives up
+    /// and runs again when the machine continues; [`LittleComputer3::step`]
+    /// returns [`Error::Paused`] then. Pausing releases the key.
+    pub fn set_pause(&mut self, pause: Option<Hotkey>) {
+        self.pause = pause;
+    }
+
+    pub fn pause(&self) -> Option<&Hotkey> {
+        self.pause.as_ref()
+    }
+
+    /// Attaches `device` to the addresses in `range`. Attached devices take
+    /// precedence over memory and the built-in devices.
+    pub fn attach_device<D>(&mut self, range: RangeInclusive<u16>, device: D)
+    where
+        D: Device + 'static,
+    {
+        self.memory.attach_device(range, Box::new(device));
+    }
+
+    /// Disassembles the words stored in `range` without side effects on
+    /// memory mapped devices.
+    pub fn disassemble(&self, range: RangeInclusive<u16>) -> Vec<Line> {
+        range
+            .map(|address| Line {
+                address,
+                word: self.memory.peek(address),
+                text: disasm::disassemble(address, self.memory.peek(address)),
+            })
+            .collect()
+    }
+
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    pub fn registers_mut(&mut self) -> &mut Registers {
+        &mut self.registers
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    pub fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    /// The number of instructions executed, clock cycles are estimated by the
+    /// [`Stats`].
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// The machine runs while the clock enable bit of the MCR is set.
+    pub fn is_halted(&self) -> bool {
+        !self.memory.bus().machine_control().clock_enabled()
+    }
+
+    /// Sets the MCR clock enable bit again so a halted machine continues.
+    pub fn resume(&mut self) {
+        self.memory
+            .bus_mut()
+            .machine_control_mut()
+            .set_clock_enabled(true);
+    }
+
+    /// Fetches, decodes and executes exactly one instruction.
+    pub fn step(&mut self) -> Result<StepOutcome, Error> {
+        if self.is_halted() {
+            return Err(Error::MachineHalted);
+        }
+
+        let before =
+            (self.tracer.is_some() || self.history.capacity() > 0).then(|| self.registers.clone());
+        self.clock.set(self.cycles);
+        // drop accesses made outside of a step, e.g. while loading a program
+        self.memory.take_accesses();
+        self.memory.set_recording(
+            self.record_accesses
+                || before.is_some()
+                || self.stats.is_some()
+                || self.profiler.is_some(),
+        );
+        self.interrupts.clear();
+        self.memory
+            .poll_devices(&mut self.input, &mut self.interrupts);
+        let interrupt = self.interrupts.take(self.registers.priority());
+        if let Some(interrupt) = interrupt {
+            let return_address = self.registers.get_pc();
+            interrupt::initiate(
                 &mut self.registers,
                 &mut self.memory,
-                &mut input,
-                &mut output,
-            )?;
+                &mut self.input,
+                interrupt.vector,
+                Some(interrupt.priority),
+                return_address,
+            );
+        }
+
+        let pc_before = self.registers.get_pc();
+        let instruction: Instructions = self.memory.fetch(pc_before, &mut self.input).try_into()?;
+        self.registers.set_pc(pc_before.wrapping_add(1));
+        let state = match (&instruction, self.trap_mode) {
+            (Instructions::Trap(routine), TrapMode::Vectored) => {
+                interrupt::trap(
+                    &mut self.registers,
+                    &mut self.memory,
+                    &mut self.input,
+                    routine.vector(),
+                );
+                ExecutionState::Running
+            }
+            _ => {
+                let mut input = PausableInput {
+                    input: self.input.as_mut(),
+                    pause: self.pause.as_ref(),
+                };
+                match instruction.execute(
+                    &mut self.registers,
+                    &mut self.memory,
+                    &mut input,
+                    &mut self.output,
+                ) {
+                    Err(Error::InputExhausted) if self.eof_policy() == EofPolicy::Halt => {
+                        ExecutionState::Halted
+                    }
+                    Err(Error::Paused) => {
+                        // run the TRAP again once the machine continues
+                        self.registers.set_pc(pc_before);
+                        if let Some(pause) = &self.pause {
+                            pause.take();
+                        }
+                        return Err(Error::Paused);
+                    }
+                    result => result?,
+                }
+            }
+        };
+        self.cycles += 1;
+
+        let display = self.memory.bus_mut().display_mut().take_output();
+        if !display.is_empty() {
+            self.output.write_all(&display)?;
+            self.output.flush()?;
+        }
+        let keyboard = self.memory.bus().keyboard();
+        let exhausted = keyboard.is_exhausted();
+        let state = match keyboard.eof_policy() {
+            EofPolicy::Halt if exhausted => ExecutionState::Halted,
+            _ => state,
+        };
+        if state == ExecutionState::Halted {
+            self.memory
+                .bus_mut()
+                .machine_control_mut()
+                .set_clock_enabled(false);
+        }
+
+        let outcome = StepOutcome {
+            interrupt,
+            instruction,
+            pc_before,
+            pc_after: self.registers.get_pc(),
+            halted: self.is_halted(),
+            accesses: self.memory.take_accesses(),
+        };
+        if let Some(stats) = &mut self.stats {
+            stats.record(&outcome);
+        }
+        if let Some(profiler) = &mut self.profiler {
+            profiler.record(&outcome);
         }
+        if let Some(before) = before {
+            if let Some(tracer) = &mut self.tracer {
+                tracer.record(self.cycles, &before, &self.registers, &outcome)?;
+            }
+            self.history
+                .push(Undo::new(self.cycles, pc_before, before, &outcome.accesses));
+        }
+        // reported once the step it executed is recorded
+        if exhausted && self.eof_policy() == EofPolicy::Error {
+            return Err(Error::InputExhausted);
+        }
+        Ok(outcome)
+    }
 
+    /// Captures the complete state of the machine.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            cycles: self.cycles,
+            trap_mode: self.trap_mode,
+            registers: SNAPSHOT_REGISTERS.map(|register| self.registers.get(register)),
+            memory: self.memory.cells().to_vec(),
+            devices: self
+                .memory
+                .bus()
+                .devices()
+                .map(|device| device.save())
+                .collect(),
+        }
+    }
+
+    /// Puts the machine back into the state of `snapshot`. The machine needs
+    /// the same attached devices as the one the snapshot was taken of. The
+    /// undo history is cleared.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), Error> {
+        let devices = self.memory.bus().devices().count();
+        if snapshot.devices.len() != devices {
+            return Err(Error::InvalidSnapshot(format!(
+                "taken with {} devices, the machine has {devices}",
+                snapshot.devices.len()
+            )));
+        }
+        if snapshot.memory.len() != self.memory.cells().len() {
+            return Err(Error::InvalidSnapshot("wrong memory size".to_string()));
+        }
+
+        for (device, state) in self.memory.bus_mut().devices_mut().zip(&snapshot.devices) {
+            device.load(state)?;
+        }
+        self.memory.cells_mut().copy_from_slice(&snapshot.memory);
+        for (register, value) in SNAPSHOT_REGISTERS.into_iter().zip(snapshot.registers) {
+            self.registers.set(register, value);
+        }
+        self.cycles = snapshot.cycles;
+        self.trap_mode = snapshot.trap_mode;
+        self.history = History::new(self.history.capacity());
         Ok(())
     }
+
+    /// Undoes the most recent instruction in the history, restoring the
+    /// registers and the memory it wrote. Device state such as consumed
+    /// keys or printed characters is not undone. `None` once the history is
+    /// exhausted.
+    pub fn step_back(&mut self) -> Option<Undo> {
+        let undo = self.history.pop()?;
+        for write in undo.writes().iter().rev() {
+            self.memory.restore(write.address, write.old);
+        }
+        self.registers = undo.registers().clone();
+        self.cycles = undo.cycle() - 1;
+        self.resume();
+        Some(undo)
+    }
+
+    /// Executes at most `count` instructions, stopping early if the machine halts.
+    pub fn run_for(&mut self, count: u64) -> Result<RunOutcome, Error> {
+        for _ in 0..count {
+            if self.is_halted() || self.step_unless_paused()?.is_none() {
+                break;
+            }
+        }
+
+        Ok(self.outcome())
+    }
+
+    /// Executes instructions until `predicate` returns `true` for a step or the machine halts.
+    pub fn run_until<P>(&mut self, mut predicate: P) -> Result<RunOutcome, Error>
+    where
+        P: FnMut(&StepOutcome) -> bool,
+    {
+        while !self.is_halted() {
+            match self.step_unless_paused()? {
+                Some(outcome) if !predicate(&outcome) => {}
+                _ => break,
+            }
+        }
+
+        Ok(self.outcome())
+    }
+
+    pub fn execute_program(&mut self) -> Result<RunOutcome, Error> {
+        while !self.is_halted() && self.step_unless_paused()?.is_some() {}
+
+        Ok(self.outcome())
+    }
+
+    /// Executes one instruction, or returns `None` if the machine was paused.
+    fn step_unless_paused(&mut self) -> Result<Option<StepOutcome>, Error> {
+        if self.pause.as_ref().is_some_and(Hotkey::take) {
+            return Ok(None);
+        }
+        match self.step() {
+            Ok(outcome) => Ok(Some(outcome)),
+            Err(Error::Paused) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn outcome(&self) -> RunOutcome {
+        let pc = self.registers.get_pc();
+        if self.is_halted() {
+            RunOutcome::Halted {
+                pc,
+                cycles: self.cycles,
+            }
+        } else {
+            RunOutcome::Paused {
+                pc,
+                cycles: self.cycles,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, Read};
+
+    use crate::{
+        lc3::{
+            device::Device,
+            error::Error,
+            instructions::Instructions,
+            interrupt::KEYBOARD_INTERRUPT,
+            memory::{Access, AccessKind, MemoryMappedReg},
+            registers::RegistersEnum,
+            replay::Recording,
+            snapshot::Snapshot,
+            stats::{CostModel, Stats},
+        },
+        vm::{memory::MemoryTrait, registers::RegistersTrait},
+    };
+
+    use super::{EofPolicy, Hotkey, LittleComputer3, RunOutcome, Shared, TrapMode};
+
+    // .ORIG x3000, ADD R0, R0, #1 (three times)
+    const PROGRAM: [u8; 8] = [0x30, 0x00, 0x10, 0x21, 0x10, 0x21, 0x10, 0x21];
+    // .ORIG x3000, ADD R0, R0, #1, HALT, ADD R0, R0, #1
+    const HALTING_PROGRAM: [u8; 8] = [0x30, 0x00, 0x10, 0x21, 0xF0, 0x25, 0x10, 0x21];
+
+    #[test]
+    fn test_step() {
+        let mut lc3 = LittleComputer3::default();
+        lc3.load_program(Cursor::new(PROGRAM)).unwrap();
+        lc3.set_record_accesses(true);
+
+        assert_eq!(
+            "x3000  x1021  ADD R0, R0, #1",
+            lc3.disassemble(0x3000..=0x3000)[0].to_string()
+        );
+        let outcome = lc3.step().unwrap();
+        assert!(matches!(outcome.instruction, Instructions::Add { .. }));
+        assert_eq!(0x3000, outcome.pc_before);
+        assert_eq!(0x3001, outcome.pc_after);
+        assert!(!outcome.halted);
+        assert_eq!(
+            vec![Access {
+                kind: AccessKind::Fetch,
+                address: 0x3000,
+                old: 0x1021,
+                value: 0x1021
+            }],
+            outcome.accesses
+        );
+        assert_eq!(1, lc3.registers().get(RegistersEnum::R0));
+        assert_eq!(1, lc3.cycles());
+
+        lc3.set_record_accesses(false);
+        assert!(lc3.step().unwrap().accesses.is_empty());
+    }
+
+    #[test]
+    fn test_run_for() {
+        let mut lc3 = LittleComputer3::default();
+        lc3.load_program(Cursor::new(PROGRAM)).unwrap();
+
+        let outcome = lc3.run_for(2).unwrap();
+        assert_eq!(
+            RunOutcome::Paused {
+                pc: 0x3002,
+                cycles: 2
+            },
+            outcome
+        );
+        assert_eq!(2, lc3.registers().get(RegistersEnum::R0));
+    }
+
+    #[test]
+    fn test_run_until() {
+        let mut lc3 = LittleComputer3::default();
+        lc3.load_program(Cursor::new(PROGRAM)).unwrap();
+
+        let outcome = lc3.run_until(|step| step.pc_after == 0x3003).unwrap();
+        assert_eq!(
+            RunOutcome::Paused {
+                pc: 0x3003,
+                cycles: 3
+            },
+            outcome
+        );
+        assert_eq!(3, lc3.registers().get(RegistersEnum::R0));
+    }
+
+    #[test]
+    fn test_halt() {
+        let mut lc3 = LittleComputer3::default();
+        lc3.load_program(Cursor::new(HALTING_PROGRAM)).unwrap();
+
+        let outcome = lc3.execute_program().unwrap();
+        assert_eq!(
+            RunOutcome::Halted {
+                pc: 0x3002,
+                cycles: 2
+            },
+            outcome
+        );
+        assert!(lc3.is_halted());
+        assert_eq!(1, lc3.registers().get(RegistersEnum::R0));
+        assert!(matches!(lc3.step(), Err(Error::MachineHalted)));
+    }
+
+    #[test]
+    fn test_keyboard_interrupt() {
+        struct Pending;
+
+        impl Read for Pending {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::WouldBlock.into())
+            }
+        }
+
+        let mut lc3 = LittleComputer3::builder().input(Pending).build().unwrap();
+        lc3.load_program(Cursor::new(PROGRAM)).unwrap();
+        // handler at x1000: LDI R0, #1 (KBDR via x1002), RTI
+        lc3.memory.write(0x0180, 0x1000);
+        lc3.memory.write(0x1000, 0xA001);
+        lc3.memory.write(0x1001, 0x8000);
+        lc3.memory.write(0x1002, MemoryMappedReg::Kbdr as u16);
+        lc3.memory.write(MemoryMappedReg::Kbsr as u16, 0x4000);
+        lc3.memory.bus_mut().keyboard_mut().push_key(b'a');
+
+        let outcome = lc3.step().unwrap();
+        assert_eq!(Some(KEYBOARD_INTERRUPT), outcome.interrupt);
+        assert_eq!(0x1000, outcome.pc_before);
+        assert_eq!('a' as u16, lc3.registers().get(RegistersEnum::R0));
+        assert_eq!(4, lc3.registers().priority());
+        assert!(!lc3.registers().is_user_mode());
+
+        let outcome = lc3.step().unwrap();
+        assert_eq!(None, outcome.interrupt);
+        assert_eq!(0x3000, outcome.pc_after);
+        assert_eq!(0, lc3.registers().priority());
+        assert!(lc3.registers().is_user_mode());
+    }
+
+    #[test]
+    fn test_vectored_trap() {
+        // .ORIG x3000, TRAP x26, HALT
+        const PROGRAM: [u8; 6] = [0x30, 0x00, 0xF0, 0x26, 0xF0, 0x25];
+        // .ORIG x4000, ADD R1, R1, #5, RTI
+        const ROUTINE: [u8; 6] = [0x40, 0x00, 0x12, 0x65, 0x80, 0x00];
+
+        let output = Shared::new(Vec::new());
+        let mut lc3 = LittleComputer3::builder()
+            .os()
+            .output(output.clone())
+            .build()
+            .unwrap();
+        lc3.load_program(Cursor::new(PROGRAM)).unwrap();
+        lc3.load_program(Cursor::new(ROUTINE)).unwrap();
+        lc3.memory.write(0x0026, 0x4000);
+        assert_eq!(TrapMode::Vectored, lc3.trap_mode());
+
+        lc3.step().unwrap();
+        assert_eq!(0x4000, lc3.registers().get_pc());
+        assert_eq!(0x3001, lc3.registers().get(RegistersEnum::R7));
+        assert!(!lc3.registers().is_user_mode());
+
+        let outcome = lc3.run_until(|step| step.pc_after == 0x3001).unwrap();
+        assert_eq!(
+            RunOutcome::Paused {
+                pc: 0x3001,
+                cycles: 3
+            },
+            outcome
+        );
+        assert_eq!(5, lc3.registers().get(RegistersEnum::R1));
+        assert!(lc3.registers().is_user_mode());
+
+        assert!(output.borrow().is_empty());
+
+        let outcome = lc3.execute_program().unwrap();
+        assert!(matches!(outcome, RunOutcome::Halted { .. }));
+        assert!(!lc3.registers().is_user_mode());
+        assert_eq!(b"\n--- Halting the LC-3 ---\n", output.borrow().as_slice());
+    }
+
+    #[test]
+    fn test_machine_control_register() {
+        // .ORIG x3000, LDI R1, MCR, AND R1, R1, #0, STI R1, MCR, MCR .FILL xFFFE
+        const PROGRAM: [u8; 10] = [0x30, 0x00, 0xA2, 0x02, 0x52, 0x60, 0xB2, 0x00, 0xFF, 0xFE];
+
+        let mut lc3 = LittleComputer3::default();
+        lc3.load_program(Cursor::new(PROGRAM)).unwrap();
+
+        let outcome = lc3.execute_program().unwrap();
+        assert_eq!(
+            RunOutcome::Halted {
+                pc: 0x3003,
+                cycles: 3
+            },
+            outcome
+        );
+        assert_eq!(0, lc3.registers().get(RegistersEnum::R1));
+        assert_eq!(0, lc3.memory.read(0xFFFE, &mut std::io::stdin()));
+
+        lc3.resume();
+        assert!(!lc3.is_halted());
+        assert_eq!(0x8000, lc3.memory.read(0xFFFE, &mut std::io::stdin()));
+    }
+
+    #[test]
+    fn test_builder_device() {
+        #[derive(Default)]
+        struct Latch(u16);
+
+        impl Device for Latch {
+            fn read(&mut self, _address: u16, _input: &mut dyn Read) -> u16 {
+                self.0
+            }
+
+            fn write(&mut self, _address: u16, value: u16) {
+                self.0 = value + 1;
+            }
+
+            fn peek(&self, _address: u16) -> u16 {
+                self.0
+            }
+        }
+
+        // .ORIG x3000, ADD R0, R0, #1, STI R0, DEV, LDI R1, DEV, HALT, DEV .FILL xFE10
+        const PROGRAM: [u8; 12] = [
+            0x30, 0x00, 0x10, 0x21, 0xB0, 0x02, 0xA2, 0x01, 0xF0, 0x25, 0xFE, 0x10,
+        ];
+
+        let mut lc3 = LittleComputer3::builder()
+            .device(0xFE10..=0xFE10, Latch::default())
+            .build()
+            .unwrap();
+        lc3.load_program(Cursor::new(PROGRAM)).unwrap();
+        lc3.execute_program().unwrap();
+        assert_eq!(2, lc3.registers().get(RegistersEnum::R1));
+    }
+
+    #[test]
+    fn test_wrap_around() {
+        // .ORIG xFFFF, ADD R0, R0, #1, ADD R0, R0, #1
+        const PROGRAM: [u8; 6] = [0xFF, 0xFF, 0x10, 0x21, 0x10, 0x21];
+
+        let mut lc3 = LittleComputer3::builder()
+            .input(Cursor::new([]))
+            .build()
+            .unwrap();
+        lc3.load_program(Cursor::new(PROGRAM)).unwrap();
+        assert_eq!(0x1021, lc3.memory.read(0xFFFF, &mut Cursor::new([])));
+        assert_eq!(0x1021, lc3.memory.read(0x0000, &mut Cursor::new([])));
+
+        lc3.registers.set_pc(0xFFFF);
+        let outcome = lc3.step().unwrap();
+        assert_eq!(0x0000, outcome.pc_after);
+        let outcome = lc3.step().unwrap();
+        assert_eq!(0x0001, outcome.pc_after);
+        assert_eq!(2, lc3.registers().get(RegistersEnum::R0));
+    }
+
+    #[test]
+    fn test_input_output() {
+        // .ORIG x3000, GETC, OUT, LEA R0, MSG, PUTS, HALT, MSG .STRINGZ "ok"
+        const PROGRAM: [u8; 20] = [
+            0x30, 0x00, 0xF0, 0x20, 0xF0, 0x21, 0xE0, 0x02, 0xF0, 0x22, 0xF0, 0x25, 0x00, 0x6F,
+            0x00, 0x6B, 0x00, 0x00, 0x00, 0x00,
+        ];
+
+        for os in [false, true] {
+            let output = Shared::new(Vec::new());
+            let mut builder = LittleComputer3::builder()
+                .input(Cursor::new(b"x".to_vec()))
+                .output(output.clone());
+            if os {
+                builder = builder.os();
+            }
+            let mut lc3 = builder.build().unwrap();
+            lc3.load_program(Cursor::new(PROGRAM)).unwrap();
+            lc3.execute_program().unwrap();
+
+            assert!(output.borrow().starts_with(b"xok"));
+        }
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        // .ORIG x3000, GETC, OUT, GETC, OUT, HALT
+        const PROGRAM: [u8; 12] = [
+            0x30, 0x00, 0xF0, 0x20, 0xF0, 0x21, 0xF0, 0x20, 0xF0, 0x21, 0xF0, 0x25,
+        ];
+        const KBSR: u16 = MemoryMappedReg::Kbsr as u16;
+
+        let output = Shared::new(Vec::new());
+        let mut lc3 = LittleComputer3::builder()
+            .os()
+            .input(Cursor::new(b"ab".to_vec()))
+            .output(output.clone())
+            .build()
+            .unwrap();
+        lc3.load_program(Cursor::new(PROGRAM)).unwrap();
+        // stop once the first key is queued but not yet read
+        while lc3.memory().peek(KBSR) & 0x8000 == 0 {
+            lc3.step().unwrap();
+        }
+
+        let mut file = Vec::new();
+        lc3.snapshot().write_to(&mut file).unwrap();
+        let snapshot = Snapshot::read_from(file.as_slice()).unwrap();
+        assert_eq!(lc3.snapshot(), snapshot);
+
+        let restored_output = Shared::new(Vec::new());
+        let mut restored = LittleComputer3::builder()
+            .input(Cursor::new(b"b".to_vec()))
+            .output(restored_output.clone())
+            .build()
+            .unwrap();
+        restored.restore(&snapshot).unwrap();
+        assert_eq!(lc3.cycles(), restored.cycles());
+
+        let outcome = lc3.execute_program().unwrap();
+        assert_eq!(outcome, restored.execute_program().unwrap());
+        for register in [RegistersEnum::R0, RegistersEnum::ProgramCounter] {
+            assert_eq!(
+                lc3.registers().get(register),
+                restored.registers().get(register)
+            );
+        }
+        assert!(output.borrow().starts_with(b"ab"));
+        assert_eq!(*output.borrow(), *restored_output.borrow());
+    }
+
+    #[test]
+    fn test_restore_device_mismatch() {
+        struct Unmapped;
+
+        impl Device for Unmapped {
+            fn read(&mut self, _address: u16, _input: &mut dyn Read) -> u16 {
+                0
+            }
+
+            fn write(&mut self, _address: u16, _value: u16) {}
+
+            fn peek(&self, _address: u16) -> u16 {
+                0
+            }
+        }
+
+        let mut other = LittleComputer3::builder()
+            .device(0xFE20..=0xFE20, Unmapped)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            other.restore(&LittleComputer3::default().snapshot()),
+            Err(Error::InvalidSnapshot(_))
+        ));
+    }
+
+    #[test]
+    fn test_pause() {
+        // .ORIG x3000, ADD R0, R0, #1, GETC
+        const PROGRAM: [u8; 6] = [0x30, 0x00, 0x10, 0x21, 0xF0, 0x20];
+
+        let pause = Hotkey::default();
+        let mut lc3 = LittleComputer3::builder()
+            // no key is ever pressed
+            .replay_input(Recording::default())
+            .pause(pause.clone())
+            .build()
+            .unwrap();
+        lc3.load_program(Cursor::new(PROGRAM)).unwrap();
+
+        pause.press();
+        let outcome = lc3.execute_program().unwrap();
+        assert_eq!(
+            RunOutcome::Paused {
+                pc: 0x3000,
+                cycles: 0
+            },
+            outcome
+        );
+        assert!(!pause.is_pressed());
+
+        let presser = {
+            let pause = pause.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                pause.press();
+            })
+        };
+        let outcome = lc3.execute_program().unwrap();
+        presser.join().unwrap();
+        assert_eq!(
+            RunOutcome::Paused {
+                pc: 0x3001,
+                cycles: 1
+            },
+            outcome
+        );
+
+        pause.press();
+        assert!(matches!(lc3.step(), Err(Error::Paused)));
+        assert_eq!(0x3001, lc3.registers().get_pc());
+        assert!(!pause.is_pressed());
+    }
+
+    #[test]
+    fn test_record_replay_input() {
+        // .ORIG x3000, LOOP ADD R1, R1, #1, LDI R0, KBSR, BRzp LOOP,
+        // LDI R0, KBDR, HALT, KBSR .FILL xFE00, KBDR .FILL xFE02
+        const PROGRAM: [u8; 16] = [
+            0x30, 0x00, 0x12, 0x61, 0xA0, 0x03, 0x07, 0xFD, 0xA0, 0x02, 0xF0, 0x25, 0xFE, 0x00,
+            0xFE, 0x02,
+        ];
+
+        /// Has no input for the first reads, like a user yet to type.
+        struct Delayed(u32, Cursor<Vec<u8>>);
+
+        impl Read for Delayed {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                if self.0 > 0 {
+                    self.0 -= 1;
+                    return Err(std::io::ErrorKind::WouldBlock.into());
+                }
+                self.1.read(buf)
+            }
+        }
+
+        let log = Shared::new(Vec::new());
+        let mut lc3 = LittleComputer3::builder()
+            .input(Delayed(5, Cursor::new(b"x".to_vec())))
+            .record_input(log.clone())
+            .build()
+            .unwrap();
+        lc3.load_program(Cursor::new(PROGRAM)).unwrap();
+        let recorded = lc3.execute_program().unwrap();
+        let text = String::from_utf8(log.borrow().clone()).unwrap();
+        assert_eq!("16 120\n", text);
+
+        let mut lc3 = LittleComputer3::builder()
+            .replay_input(Recording::parse(&text).unwrap())
+            .build()
+            .unwrap();
+        lc3.load_program(Cursor::new(PROGRAM)).unwrap();
+        assert_eq!(recorded, lc3.execute_program().unwrap());
+        assert_eq!(6, lc3.registers().get(RegistersEnum::R1));
+        assert_eq!('x' as u16, lc3.registers().get(RegistersEnum::R0));
+    }
+
+    #[test]
+    fn test_stats() {
+        // .ORIG x3000, LD R1, ONE, BRp SKIP, ADD R0, R0, #1, SKIP ST R1, ONE,
+        // HALT, ONE .FILL #1
+        const PROGRAM: [u8; 14] = [
+            0x30, 0x00, 0x22, 0x04, 0x02, 0x01, 0x10, 0x21, 0x32, 0x01, 0xF0, 0x25, 0x00, 0x01,
+        ];
+
+        let mut lc3 = LittleComputer3::builder()
+            .stats(Stats::with_cost_model(CostModel { memory_latency: 2 }))
+            .output(std::io::sink())
+            .build()
+            .unwrap();
+        lc3.load_program(Cursor::new(PROGRAM)).unwrap();
+        lc3.execute_program().unwrap();
+
+        let stats = lc3.stats().unwrap();
+        assert_eq!(4, stats.instructions());
+        assert_eq!(lc3.cycles(), stats.instructions());
+        assert_eq!(Some(1), stats.opcode("br"));
+        assert_eq!(Some(0), stats.opcode("ADD"));
+        assert_eq!(Some(1), stats.opcode("TRAP"));
+        assert_eq!(None, stats.opcode("NOP"));
+        assert_eq!((1, 1), (stats.reads(), stats.writes()));
+        // LD 3 + 2 + 2 * 2, BR taken 3 + 2 + 2, ST 3 + 2 + 2 * 2, HALT 3 + 2 + 2
+        assert_eq!(Some(9 + 7 + 9 + 7), stats.clock_cycles());
+        assert_eq!(None, Stats::default().clock_cycles());
+        assert!(LittleComputer3::default().stats().is_none());
+    }
+
+    #[test]
+    fn test_eof_policy() {
+        // .ORIG x3000, GETC, GETC, ADD R2, R0, #0, HALT
+        const PROGRAM: [u8; 10] = [0x30, 0x00, 0xF0, 0x20, 0xF0, 0x20, 0x14, 0x20, 0xF0, 0x25];
+
+        for os in [false, true] {
+            let run = |eof_policy| {
+                let mut builder = LittleComputer3::builder()
+                    .input(Cursor::new(b"a".to_vec()))
+                    .output(std::io::sink())
+                    .eof_policy(eof_policy);
+                if os {
+                    builder = builder.os();
+                }
+                let mut lc3 = builder.build().unwrap();
+                lc3.load_program(Cursor::new(PROGRAM)).unwrap();
+                let outcome = lc3.execute_program();
+                let r0 = lc3.registers().get(RegistersEnum::R0);
+                let r2 = lc3.registers().get(RegistersEnum::R2);
+                (outcome, r0, r2)
+            };
+
+            let (outcome, _, r2) = run(EofPolicy::Value(0xFFFF));
+            assert!(matches!(outcome, Ok(RunOutcome::Halted { .. })));
+            assert_eq!(0xFFFF, r2);
+
+            let (outcome, r0, _) = run(EofPolicy::Halt);
+            assert!(matches!(outcome, Ok(RunOutcome::Halted { .. })));
+            if !os {
+                // the OS halts in the middle of GETC, where R0 holds KBSR
+                assert_eq!('a' as u16, r0);
+            }
+
+            let (outcome, _, _) = run(EofPolicy::Error);
+            assert!(matches!(outcome, Err(Error::InputExhausted)));
+        }
+    }
+
+    #[test]
+    fn test_eof_error_records_step() {
+        // .ORIG x3000, GETC, HALT
+        const PROGRAM: [u8; 6] = [0x30, 0x00, 0xF0, 0x20, 0xF0, 0x25];
+
+        let mut lc3 = LittleComputer3::builder()
+            .input(Cursor::new(Vec::new()))
+            .output(std::io::sink())
+            .os()
+            .stats(Stats::default())
+            .history(16)
+            .build()
+            .unwrap();
+        lc3.load_program(Cursor::new(PROGRAM)).unwrap();
+        assert!(matches!(lc3.execute_program(), Err(Error::InputExhausted)));
+
+        // the instruction reading KBSR ran and is counted and undoable
+        let cycles = lc3.cycles();
+        assert_eq!(cycles, lc3.stats().unwrap().instructions());
+        assert_eq!(cycles, lc3.step_back().unwrap().cycle());
+        assert_eq!(cycles - 1, lc3.cycles());
+    }
 }
@@ -0,0 +1,57 @@
+use std::io::Read;
+
+use super::{device::Device, error::Error, snapshot::Decoder};
+
+const MCR_CLOCK_ENABLE: u16 = 1 << 15;
+
+/// Machine control register. The machine runs while its clock enable bit is set.
+#[derive(Debug)]
+pub struct MachineControl {
+    value: u16,
+}
+
+impl MachineControl {
+    pub fn clock_enabled(&self) -> bool {
+        self.value & MCR_CLOCK_ENABLE != 0
+    }
+
+    pub fn set_clock_enabled(&mut self, enabled: bool) {
+        if enabled {
+            self.value |= MCR_CLOCK_ENABLE;
+        } else {
+            self.value &= !MCR_CLOCK_ENABLE;
+        }
+    }
+}
+
+impl Default for MachineControl {
+    fn default() -> Self {
+        Self {
+            value: MCR_CLOCK_ENABLE,
+        }
+    }
+}
+
+impl Device for MachineControl {
+    fn read(&mut self, _address: u16, _input: &mut dyn Read) -> u16 {
+        self.value
+    }
+
+    fn write(&mut self, _address: u16, value: u16) {
+        self.value = value;
+    }
+
+    fn peek(&self, _address: u16) -> u16 {
+        self.value
+    }
+
+    fn save(&self) -> Vec<u8> {
+        self.value.to_be_bytes().to_vec()
+    }
+
+    fn load(&mut self, state: &[u8]) -> Result<(), Error> {
+        let mut state = Decoder::new(state);
+        self.value = state.u16()?;
+        state.finish()
+    }
+}
@@ -1,12 +1,44 @@
-use std::io::Read;
+use std::{io::Read, ops::RangeInclusive};
 
 use crate::vm::memory::MemoryTrait;
 
-pub struct Memory([u16; u16::MAX as usize]);
+use super::{
+    device::{Bus, Device},
+    interrupt::InterruptController,
+};
+
+/// One word for every 16-bit address, 0x0000 through 0xFFFF.
+pub const MEMORY_SIZE: usize = 1 << 16;
+
+pub struct Memory {
+    cells: [u16; MEMORY_SIZE],
+    bus: Bus,
+    recording: bool,
+    accesses: Vec<Access>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccessKind {
+    Fetch,
+    Read,
+    Write,
+}
+
+/// A memory access. For reads `old` and `value` are both the value read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Access {
+    pub kind: AccessKind,
+    pub address: u16,
+    pub old: u16,
+    pub value: u16,
+}
 
 pub enum MemoryMappedReg {
     Kbsr = 0xFE00,
     Kbdr = 0xFE02,
+    Dsr = 0xFE04,
+    Ddr = 0xFE06,
+    Mcr = 0xFFFE,
 }
 
 impl MemoryTrait for Memory {
@@ -16,14 +48,22 @@ impl MemoryTrait for Memory {
     where
         I: Read,
     {
-        if address == MemoryMappedReg::Kbsr as u16 {
-            self.handle_keyboard(input);
-        }
-        self.0[address as usize]
+        self.access(AccessKind::Read, address, input)
     }
 
     fn write(&mut self, address: Self::ValueType, value: Self::ValueType) {
-        self.0[address as usize] = value;
+        if self.recording {
+            self.accesses.push(Access {
+                kind: AccessKind::Write,
+                address,
+                old: self.peek(address),
+                value,
+            });
+        }
+        match self.bus.device_mut(address) {
+            Some(device) => device.write(address, value),
+            None => self.cells[address as usize] = value,
+        }
     }
 
     fn max(&self) -> Self::ValueType {
@@ -32,24 +72,97 @@ impl MemoryTrait for Memory {
 }
 
 impl Memory {
-    fn handle_keyboard<I>(&mut self, input: &mut I)
+    pub fn bus(&self) -> &Bus {
+        &self.bus
+    }
+
+    pub fn bus_mut(&mut self) -> &mut Bus {
+        &mut self.bus
+    }
+
+    /// Reads the instruction at `address`.
+    pub fn fetch<I>(&mut self, address: u16, input: &mut I) -> u16
+    where
+        I: Read,
+    {
+        self.access(AccessKind::Fetch, address, input)
+    }
+
+    fn access<I>(&mut self, kind: AccessKind, address: u16, input: &mut I) -> u16
     where
         I: Read,
     {
-        let mut buffer = [0u8; 2];
-        input.read_exact(&mut buffer).unwrap();
-
-        if buffer[0] != 0 {
-            self.write(MemoryMappedReg::Kbsr as u16, 1 << 15);
-            self.write(MemoryMappedReg::Kbdr as u16, buffer[0] as u16);
-        } else {
-            self.write(MemoryMappedReg::Kbsr as u16, 0)
+        let value = match self.bus.device_mut(address) {
+            Some(device) => device.read(address, input),
+            None => self.cells[address as usize],
+        };
+        if self.recording {
+            self.accesses.push(Access {
+                kind,
+                address,
+                old: value,
+                value,
+            });
         }
+        value
+    }
+
+    /// Whether accesses are recorded for [`Memory::take_accesses`].
+    pub fn set_recording(&mut self, recording: bool) {
+        self.recording = recording;
+    }
+
+    /// Returns the accesses recorded since the last call.
+    pub fn take_accesses(&mut self) -> Vec<Access> {
+        std::mem::take(&mut self.accesses)
+    }
+
+    /// The contents of memory. Addresses with a device attached hold
+    /// whatever was last stored there before, not the device registers.
+    pub(crate) fn cells(&self) -> &[u16; MEMORY_SIZE] {
+        &self.cells
+    }
+
+    pub(crate) fn cells_mut(&mut self) -> &mut [u16; MEMORY_SIZE] {
+        &mut self.cells
+    }
+
+    /// Puts back a value of ordinary memory without recording an access.
+    /// Device registers are left alone.
+    pub(crate) fn restore(&mut self, address: u16, value: u16) {
+        if self.bus.device(address).is_none() {
+            self.cells[address as usize] = value;
+        }
+    }
+
+    /// Reads `address` without triggering any device side effects.
+    pub fn peek(&self, address: u16) -> u16 {
+        match self.bus.device(address) {
+            Some(device) => device.peek(address),
+            None => self.cells[address as usize],
+        }
+    }
+
+    pub fn attach_device(&mut self, range: RangeInclusive<u16>, device: Box<dyn Device>) {
+        self.bus.attach(range, device);
+    }
+
+    /// Lets devices run between instructions and raise interrupt requests.
+    pub fn poll_devices<I>(&mut self, input: &mut I, interrupts: &mut InterruptController)
+    where
+        I: Read,
+    {
+        self.bus.tick(input, interrupts);
     }
 }
 
 impl Default for Memory {
     fn default() -> Self {
-        Self([0; u16::MAX as usize])
+        Self {
+            cells: [0; MEMORY_SIZE],
+            bus: Bus::default(),
+            recording: false,
+            accesses: Vec::new(),
+        }
     }
 }
@@ -0,0 +1,4 @@
+/// Object image of the operating system assembled from `assets/os.asm`.
+/// It installs the trap and interrupt vector tables and the standard
+/// service routines in the system space below 0x3000.
+pub const OS_IMAGE: &[u8] = include_bytes!("../../assets/os.obj");
@@ -0,0 +1,322 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Write,
+};
+
+use super::{
+    disasm, instructions::Instructions, machine::StepOutcome, memory::AccessKind,
+    registers::RegistersEnum,
+};
+
+/// Hot spots listed by [`Profiler::write_report`].
+const HOT_SPOTS: usize = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FrameKind {
+    /// Where profiling started, it is never left.
+    Root,
+    /// Entered by JSR or JSRR, left by RET.
+    Subroutine,
+    /// Entered by a TRAP through the vector table or an interrupt, left by RTI.
+    Service,
+}
+
+/// How often the instruction at an address was executed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HotSpot {
+    pub address: u16,
+    pub word: u16,
+    pub count: u64,
+}
+
+/// Instruction counts of a subroutine. Inclusive counts contain the
+/// subroutines it called, exclusive counts only its own instructions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SubroutineCost {
+    pub entry: u16,
+    pub calls: u64,
+    pub inclusive: u64,
+    pub exclusive: u64,
+}
+
+/// Attributes every executed instruction to its address and to the
+/// subroutines it ran in. JSR and JSRR enter a subroutine and RET leaves it;
+/// TRAPs through the vector table and interrupts enter a service routine,
+/// which RTI leaves. Subroutines are identified by their entry address.
+/// Attach it with
+/// [`LittleComputer3Builder::profiler`](super::machine::LittleComputer3Builder::profiler).
+#[derive(Clone, Debug, Default)]
+pub struct Profiler {
+    instructions: u64,
+    addresses: HashMap<u16, HotSpot>,
+    /// Entry addresses of the active frames, outermost first.
+    stack: Vec<u16>,
+    kinds: Vec<FrameKind>,
+    /// Instructions executed per stack.
+    stacks: HashMap<Vec<u16>, u64>,
+    calls: HashMap<u16, u64>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    pub(crate) fn record(&mut self, step: &StepOutcome) {
+        if step.interrupt.is_some() {
+            self.enter(step.pc_before, FrameKind::Service);
+        }
+        if self.stack.is_empty() {
+            self.enter(step.pc_before, FrameKind::Root);
+        }
+
+        self.instructions += 1;
+        let word = step
+            .accesses
+            .iter()
+            .find(|access| access.kind == AccessKind::Fetch)
+            .map_or(0, |access| access.value);
+        let spot = self.addresses.entry(step.pc_before).or_insert(HotSpot {
+            address: step.pc_before,
+            word,
+            count: 0,
+        });
+        spot.word = word;
+        spot.count += 1;
+        match self.stacks.get_mut(self.stack.as_slice()) {
+            Some(count) => *count += 1,
+            None => {
+                self.stacks.insert(self.stack.clone(), 1);
+            }
+        }
+
+        match &step.instruction {
+            Instructions::JumpRegister(_) => self.enter(step.pc_after, FrameKind::Subroutine),
+            Instructions::Trap(_) if step.pc_after != step.pc_before.wrapping_add(1) => {
+                self.enter(step.pc_after, FrameKind::Service)
+            }
+            Instructions::Jump {
+                source: RegistersEnum::R7,
+            } if self.kinds.last() != Some(&FrameKind::Root) => {
+                self.stack.pop();
+                self.kinds.pop();
+            }
+            Instructions::RTI => {
+                if let Some(frame) = self
+                    .kinds
+                    .iter()
+                    .rposition(|kind| *kind == FrameKind::Service)
+                {
+                    self.stack.truncate(frame);
+                    self.kinds.truncate(frame);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn enter(&mut self, entry: u16, kind: FrameKind) {
+        self.stack.push(entry);
+        self.kinds.push(kind);
+        *self.calls.entry(entry).or_default() += 1;
+    }
+
+    /// The executed addresses, most executed first.
+    pub fn hot_spots(&self) -> Vec<HotSpot> {
+        let mut spots: Vec<_> = self.addresses.values().copied().collect();
+        spots.sort_by_key(|spot| (std::cmp::Reverse(spot.count), spot.address));
+        spots
+    }
+
+    /// Every subroutine, the one with the highest inclusive count first.
+    pub fn subroutines(&self) -> Vec<SubroutineCost> {
+        let mut costs = BTreeMap::new();
+        for (stack, count) in &self.stacks {
+            for (index, entry) in stack.iter().enumerate() {
+                let cost = costs.entry(*entry).or_insert(SubroutineCost {
+                    entry: *entry,
+                    calls: self.calls.get(entry).copied().unwrap_or_default(),
+                    inclusive: 0,
+                    exclusive: 0,
+                });
+                // count recursive calls once
+                if !stack[..index].contains(entry) {
+                    cost.inclusive += count;
+                }
+                if index == stack.len() - 1 {
+                    cost.exclusive += count;
+                }
+            }
+        }
+        let mut costs: Vec<_> = costs.into_values().collect();
+        costs.sort_by_key(|cost| (std::cmp::Reverse(cost.inclusive), cost.entry));
+        costs
+    }
+
+    /// Writes the most executed addresses and the cost of every subroutine.
+    pub fn write_report(
+        &self,
+        symbols: &BTreeMap<String, u16>,
+        mut output: impl Write,
+    ) -> std::io::Result<()> {
+        let share = |count: u64| 100.0 * count as f64 / self.instructions.max(1) as f64;
+
+        writeln!(output, "instructions {}", self.instructions)?;
+        writeln!(output)?;
+        writeln!(output, "     count   share  address")?;
+        for spot in self.hot_spots().into_iter().take(HOT_SPOTS) {
+            writeln!(
+                output,
+                "{:>10} {:>6.1}%  {:<24} {}",
+                spot.count,
+                share(spot.count),
+                location(symbols, spot.address),
+                disasm::disassemble(spot.address, spot.word)
+            )?;
+        }
+        writeln!(output)?;
+        writeln!(output, "     calls   inclusive   exclusive  subroutine")?;
+        for cost in self.subroutines() {
+            writeln!(
+                output,
+                "{:>10} {:>11} {:>11}  {} ({:.1}%)",
+                cost.calls,
+                cost.inclusive,
+                cost.exclusive,
+                name(symbols, cost.entry),
+                share(cost.inclusive)
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes one line per stack, its frames separated by `;` and followed by
+    /// the instructions executed in it, as read by flamegraph tools.
+    pub fn write_folded(
+        &self,
+        symbols: &BTreeMap<String, u16>,
+        mut output: impl Write,
+    ) -> std::io::Result<()> {
+        let mut lines: Vec<_> = self
+            .stacks
+            .iter()
+            .map(|(stack, count)| {
+                let frames: Vec<_> = stack.iter().map(|entry| name(symbols, *entry)).collect();
+                (frames.join(";"), count)
+            })
+            .collect();
+        lines.sort();
+        for (stack, count) in lines {
+            writeln!(output, "{stack} {count}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The label at `address`, or the address itself.
+fn name(symbols: &BTreeMap<String, u16>, address: u16) -> String {
+    symbols
+        .iter()
+        .find(|(_, symbol)| **symbol == address)
+        .map_or_else(|| format!("x{address:04X}"), |(name, _)| name.clone())
+}
+
+/// `address` relative to the closest label before it.
+fn location(symbols: &BTreeMap<String, u16>, address: u16) -> String {
+    let closest = symbols
+        .iter()
+        .filter(|(_, symbol)| **symbol <= address)
+        .max_by_key(|(_, symbol)| **symbol);
+    match closest {
+        Some((name, symbol)) if *symbol == address => format!("x{address:04X} <{name}>"),
+        Some((name, symbol)) => format!("x{address:04X} <{name}+{}>", address - symbol),
+        None => format!("x{address:04X}"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::lc3::{asm::assemble, machine::LittleComputer3};
+
+    use super::{Profiler, SubroutineCost};
+
+    const SOURCE: &str = "
+            .ORIG x3000
+    START   JSR TWICE
+            JSR TWICE
+            HALT
+    TWICE   ST R7, SAVE
+            JSR INC
+            JSR INC
+            LD R7, SAVE
+            RET
+    INC     ADD R0, R0, #1
+            RET
+    SAVE    .BLKW 1
+            .END
+    ";
+
+    fn profile(os: bool) -> (LittleComputer3, std::collections::BTreeMap<String, u16>) {
+        let program = assemble(SOURCE).unwrap();
+        let mut builder = LittleComputer3::builder()
+            .output(std::io::sink())
+            .profiler(Profiler::new());
+        if os {
+            builder = builder.os();
+        }
+        let mut lc3 = builder.build().unwrap();
+        lc3.load_program(Cursor::new(program.to_bytes())).unwrap();
+        lc3.execute_program().unwrap();
+        (lc3, program.symbols().clone())
+    }
+
+    #[test]
+    fn test_subroutines() {
+        let (lc3, symbols) = profile(false);
+        let profiler = lc3.profiler().unwrap();
+        assert_eq!(21, profiler.instructions());
+
+        let cost = |entry| {
+            let cost = |cost: &&SubroutineCost| cost.entry == symbols[entry];
+            let cost = *profiler.subroutines().iter().find(cost).unwrap();
+            (cost.calls, cost.inclusive, cost.exclusive)
+        };
+        assert_eq!((1, 21, 3), cost("START"));
+        assert_eq!((2, 18, 10), cost("TWICE"));
+        assert_eq!((4, 8, 8), cost("INC"));
+
+        let spot = profiler.hot_spots()[0];
+        assert_eq!((symbols["INC"], 4), (spot.address, spot.count));
+
+        let mut folded = Vec::new();
+        profiler.write_folded(&symbols, &mut folded).unwrap();
+        assert_eq!(
+            "START 3\nSTART;TWICE 10\nSTART;TWICE;INC 8\n",
+            String::from_utf8(folded).unwrap()
+        );
+
+        let mut report = Vec::new();
+        profiler.write_report(&symbols, &mut report).unwrap();
+        let report = String::from_utf8(report).unwrap();
+        assert!(report.contains("x3008 <INC>"));
+        assert!(report.contains("x3009 <INC+1>"));
+    }
+
+    #[test]
+    fn test_service_routines() {
+        let (lc3, symbols) = profile(true);
+        let profiler = lc3.profiler().unwrap();
+
+        // HALT runs in the operating system, which never returns from it
+        let mut folded = Vec::new();
+        profiler.write_folded(&symbols, &mut folded).unwrap();
+        let folded = String::from_utf8(folded).unwrap();
+        assert!(folded.starts_with("START 3\nSTART;TWICE 10\nSTART;TWICE;INC 8\nSTART;x"));
+    }
+}
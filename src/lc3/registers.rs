@@ -3,8 +3,13 @@ use crate::vm::registers::RegistersTrait;
 use super::error::Error;
 
 pub const PROGRAM_START: u16 = 0x3000;
+pub const SUPERVISOR_STACK_START: u16 = 0x3000;
 
-#[derive(Clone, Copy, Debug)]
+pub const PSR_PRIVILEGE: u16 = 1 << 15;
+pub const PSR_PRIORITY: u16 = 0x7 << 8;
+pub const PSR_CONDITION: u16 = 0x7;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u8)]
 pub enum RegistersEnum {
     R0 = 0,
@@ -17,6 +22,9 @@ pub enum RegistersEnum {
     R7,
     ProgramCounter,
     Condition,
+    ProcessorStatus,
+    SavedUserStackPointer,
+    SavedSupervisorStackPointer,
 }
 
 impl TryFrom<u16> for RegistersEnum {
@@ -34,6 +42,9 @@ impl TryFrom<u16> for RegistersEnum {
             7 => Ok(RegistersEnum::R7),
             8 => Ok(RegistersEnum::ProgramCounter),
             9 => Ok(RegistersEnum::Condition),
+            10 => Ok(RegistersEnum::ProcessorStatus),
+            11 => Ok(RegistersEnum::SavedUserStackPointer),
+            12 => Ok(RegistersEnum::SavedSupervisorStackPointer),
             _ => Err(Error::UnknownRegister(value)),
         }
     }
@@ -45,32 +56,60 @@ enum ConditionFlag {
     Negative = 1 << 2,
 }
 
-#[derive(Debug)]
-pub struct Registers([u16; 10]);
+// The condition codes live in the low bits of the processor status register,
+// so the `Condition` slot of the array is never used.
+#[derive(Clone, Debug)]
+pub struct Registers([u16; 13]);
 
 impl Default for Registers {
     fn default() -> Self {
-        let mut registers = Self([0; 10]);
+        let mut registers = Self([0; 13]);
         registers.set(RegistersEnum::ProgramCounter, PROGRAM_START);
+        registers.set(RegistersEnum::ProcessorStatus, PSR_PRIVILEGE);
+        registers.set(
+            RegistersEnum::SavedSupervisorStackPointer,
+            SUPERVISOR_STACK_START,
+        );
         registers
     }
 }
 
+impl Registers {
+    pub fn is_user_mode(&self) -> bool {
+        self.get(RegistersEnum::ProcessorStatus) & PSR_PRIVILEGE != 0
+    }
+
+    pub fn priority(&self) -> u16 {
+        (self.get(RegistersEnum::ProcessorStatus) & PSR_PRIORITY) >> 8
+    }
+}
+
 impl RegistersTrait for Registers {
     type RegisterSet = RegistersEnum;
     type ValueType = u16;
 
     fn get(&self, register: Self::RegisterSet) -> Self::ValueType {
-        self.0[register as usize]
+        match register {
+            RegistersEnum::Condition => {
+                self.0[RegistersEnum::ProcessorStatus as usize] & PSR_CONDITION
+            }
+            _ => self.0[register as usize],
+        }
     }
 
     fn set(&mut self, register: Self::RegisterSet, value: Self::ValueType) {
-        self.0[register as usize] = value;
+        match register {
+            RegistersEnum::Condition => {
+                let psr = &mut self.0[RegistersEnum::ProcessorStatus as usize];
+                *psr = (*psr & !PSR_CONDITION) | (value & PSR_CONDITION);
+            }
+            _ => self.0[register as usize] = value,
+        }
     }
 
     fn next_instruction(&mut self) -> Self::ValueType {
         let address = self.get(RegistersEnum::ProgramCounter);
-        self.set(RegistersEnum::ProgramCounter, address + 1);
+        self.set(RegistersEnum::ProgramCounter, address.wrapping_add(1));
         address
     }
 
@@ -114,4 +153,19 @@ mod test {
         registers.set(super::RegistersEnum::R0, 12);
         assert_eq!(12, registers.get(super::RegistersEnum::R0));
     }
+
+    #[test]
+    fn test_condition_in_processor_status() {
+        let mut registers = super::Registers::default();
+        assert!(registers.is_user_mode());
+        assert_eq!(0, registers.priority());
+
+        registers.set(super::RegistersEnum::R0, 0x8000);
+        registers.update_flags(super::RegistersEnum::R0);
+        assert_eq!(0b100, registers.get(super::RegistersEnum::Condition));
+        assert_eq!(
+            super::PSR_PRIVILEGE | 0b100,
+            registers.get(super::RegistersEnum::ProcessorStatus)
+        );
+    }
 }
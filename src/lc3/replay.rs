@@ -0,0 +1,264 @@
+use std::{
+    cell::Cell,
+    collections::VecDeque,
+    fmt::Display,
+    io::{ErrorKind, Read, Write},
+    rc::Rc,
+    str::FromStr,
+};
+
+use super::error::Error;
+
+/// One input byte and the cycle at which the machine read it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InputEvent {
+    pub cycle: u64,
+    /// `None` marks the end of the input.
+    pub byte: Option<u8>,
+}
+
+impl Display for InputEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.byte {
+            Some(byte) => write!(f, "{} {}", self.cycle, byte),
+            None => write!(f, "{} eof", self.cycle),
+        }
+    }
+}
+
+impl FromStr for InputEvent {
+    type Err = ();
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let (cycle, byte) = line.split_once(' ').ok_or(())?;
+        let byte = match byte.trim() {
+            "eof" => None,
+            byte => Some(byte.parse().map_err(|_| ())?),
+        };
+        Ok(Self {
+            cycle: cycle.parse().map_err(|_| ())?,
+            byte,
+        })
+    }
+}
+
+/// The console input of a run, written by
+/// [`LittleComputer3::record_input`](super::machine::LittleComputer3::record_input)
+/// and fed back by
+/// [`LittleComputer3::replay_input`](super::machine::LittleComputer3::replay_input).
+///
+/// A recording is text with one event per line: the number of instructions
+/// executed before the byte was read, a space and the byte in decimal, or
+/// `eof` once the input ended. Empty lines and lines starting with `#` are
+/// ignored:
+///
+/// ```text
+/// # lc3 input recording
+/// 1520 104
+/// 1977 105
+/// 2410 eof
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Recording {
+    events: Vec<InputEvent>,
+}
+
+impl Recording {
+    pub fn new(events: Vec<InputEvent>) -> Self {
+        Self { events }
+    }
+
+    pub fn events(&self) -> &[InputEvent] {
+        &self.events
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut events: Vec<InputEvent> = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |reason| Error::InvalidRecording(format!("line {}: {reason}", index + 1));
+            let event: InputEvent = line
+                .parse()
+                .map_err(|_| invalid("expected a cycle and a byte"))?;
+            if events.last().is_some_and(|last| last.cycle > event.cycle) {
+                return Err(invalid("cycles must not decrease"));
+            }
+            events.push(event);
+        }
+        Ok(Self { events })
+    }
+
+    pub fn read_from(mut input: impl Read) -> Result<Self, Error> {
+        let mut text = String::new();
+        input.read_to_string(&mut text)?;
+        Self::parse(&text)
+    }
+}
+
+impl Display for Recording {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.events
+            .iter()
+            .try_for_each(|event| writeln!(f, "{event}"))
+    }
+}
+
+/// Passes input through and logs every byte with the cycle it was read at.
+pub(crate) struct Recorder {
+    input: Box<dyn Read>,
+    clock: Rc<Cell<u64>>,
+    log: Box<dyn Write>,
+    ended: bool,
+}
+
+impl Recorder {
+    pub(crate) fn new(input: Box<dyn Read>, clock: Rc<Cell<u64>>, log: Box<dyn Write>) -> Self {
+        Self {
+            input,
+            clock,
+            log,
+            ended: false,
+        }
+    }
+
+    fn log(&mut self, byte: Option<u8>) -> std::io::Result<()> {
+        let cycle = self.clock.get();
+        writeln!(self.log, "{}", InputEvent { cycle, byte })?;
+        // keep the log complete if the run is killed
+        self.log.flush()
+    }
+}
+
+impl Read for Recorder {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let count = self.input.read(buf)?;
+        if count == 0 && !buf.is_empty() && !self.ended {
+            self.ended = true;
+            self.log(None)?;
+        }
+        for &byte in &buf[..count] {
+            self.log(Some(byte))?;
+        }
+        Ok(count)
+    }
+}
+
+/// Hands out the bytes of a [`Recording`] once the machine reaches the cycle
+/// they were recorded at, reporting [`ErrorKind::WouldBlock`] before. After
+/// the last byte of a recording without an end of input, no further key is
+/// ever pressed.
+pub(crate) struct Replayer {
+    events: VecDeque<InputEvent>,
+    clock: Rc<Cell<u64>>,
+}
+
+impl Replayer {
+    pub(crate) fn new(recording: Recording, clock: Rc<Cell<u64>>) -> Self {
+        Self {
+            events: recording.events.into(),
+            clock,
+        }
+    }
+}
+
+impl Read for Replayer {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        match self.events.front() {
+            Some(event) if event.cycle <= self.clock.get() => match event.byte {
+                Some(byte) => {
+                    buf[0] = byte;
+                    self.events.pop_front();
+                    Ok(1)
+                }
+                None => Ok(0),
+            },
+            _ => Err(ErrorKind::WouldBlock.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        cell::Cell,
+        io::{Cursor, ErrorKind, Read},
+        rc::Rc,
+    };
+
+    use crate::lc3::{console::Shared, error::Error};
+
+    use super::{InputEvent, Recorder, Recording, Replayer};
+
+    #[test]
+    fn test_parse() {
+        let recording = Recording::parse("# keys\n12 104\n\n12 105\n30 eof\n").unwrap();
+        assert_eq!(
+            vec![
+                InputEvent {
+                    cycle: 12,
+                    byte: Some(104)
+                },
+                InputEvent {
+                    cycle: 12,
+                    byte: Some(105)
+                },
+                InputEvent {
+                    cycle: 30,
+                    byte: None
+                },
+            ],
+            recording.events()
+        );
+        assert_eq!("12 104\n12 105\n30 eof\n", recording.to_string());
+
+        for text in ["12", "12 256", "x 1", "12 1\n11 2"] {
+            assert!(matches!(
+                Recording::parse(text),
+                Err(Error::InvalidRecording(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_record_replay() {
+        let clock = Rc::new(Cell::new(0));
+        let log = Shared::new(Vec::new());
+        let mut recorder = Recorder::new(
+            Box::new(Cursor::new(b"ab".to_vec())),
+            clock.clone(),
+            Box::new(log.clone()),
+        );
+        let mut buffer = [0; 1];
+        for (cycle, expected) in [(3, 1), (7, 1), (9, 0)] {
+            clock.set(cycle);
+            assert_eq!(expected, recorder.read(&mut buffer).unwrap());
+        }
+        let text = String::from_utf8(log.borrow().clone()).unwrap();
+        assert_eq!("3 97\n7 98\n9 eof\n", text);
+
+        let clock = Rc::new(Cell::new(0));
+        let mut replayer = Replayer::new(Recording::parse(&text).unwrap(), clock.clone());
+        let mut read = |cycle| {
+            clock.set(cycle);
+            match replayer.read(&mut buffer) {
+                Ok(0) => Some(None),
+                Ok(_) => Some(Some(buffer[0])),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => None,
+                Err(e) => panic!("{e}"),
+            }
+        };
+        assert_eq!(None, read(2));
+        assert_eq!(Some(Some(b'a')), read(3));
+        assert_eq!(None, read(6));
+        assert_eq!(Some(Some(b'b')), read(8));
+        assert_eq!(None, read(8));
+        assert_eq!(Some(None), read(9));
+    }
+}
@@ -0,0 +1,196 @@
+use std::io::{Read, Write};
+
+use super::{error::Error, machine::TrapMode, memory::MEMORY_SIZE};
+
+pub const SNAPSHOT_MAGIC: &[u8; 4] = b"LC3S";
+pub const SNAPSHOT_VERSION: u16 = 1;
+
+/// The complete state of a [`LittleComputer3`](super::machine::LittleComputer3),
+/// taken with `snapshot()` and put back with `restore()`.
+///
+/// A snapshot file is big endian, like object files:
+///
+/// | field      | encoding                                                    |
+/// |------------|-------------------------------------------------------------|
+/// | magic      | `LC3S`                                                      |
+/// | version    | u16, currently 1                                            |
+/// | cycles     | u64                                                         |
+/// | trap mode  | u8, 0 native, 1 vectored                                    |
+/// | registers  | 12 × u16: R0-R7, PC, PSR, saved USP, saved SSP              |
+/// | memory     | 65536 × u16, the contents of the I/O page are device state  |
+/// | devices    | u16 count, then per device a u32 length and its state       |
+///
+/// The devices are the keyboard, the display and the machine control
+/// register, followed by attached devices in the order they were attached.
+/// Their states are:
+///
+/// - keyboard: u8 flags (bit 0 interrupt enable, bit 1 input exhausted),
+///   u16 KBDR, u32 count and the queued keys
+/// - display: u32 instructions it stays busy for, u16 DDR
+/// - machine control: u16 MCR
+///
+/// Settings such as the EOF policy and the display latency are not part of
+/// a snapshot.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    pub(crate) cycles: u64,
+    pub(crate) trap_mode: TrapMode,
+    pub(crate) registers: [u16; 12],
+    pub(crate) memory: Vec<u16>,
+    pub(crate) devices: Vec<Vec<u8>>,
+}
+
+impl Snapshot {
+    pub fn write_to(&self, mut output: impl Write) -> Result<(), Error> {
+        let mut bytes = Vec::with_capacity(2 * MEMORY_SIZE + 64);
+        bytes.extend(SNAPSHOT_MAGIC);
+        bytes.extend(SNAPSHOT_VERSION.to_be_bytes());
+        bytes.extend(self.cycles.to_be_bytes());
+        bytes.push(match self.trap_mode {
+            TrapMode::Native => 0,
+            TrapMode::Vectored => 1,
+        });
+        for word in self.registers.iter().chain(&self.memory) {
+            bytes.extend(word.to_be_bytes());
+        }
+        bytes.extend((self.devices.len() as u16).to_be_bytes());
+        for state in &self.devices {
+            bytes.extend((state.len() as u32).to_be_bytes());
+            bytes.extend(state);
+        }
+        output.write_all(&bytes)?;
+        Ok(())
+    }
+
+    pub fn read_from(mut input: impl Read) -> Result<Self, Error> {
+        let mut bytes = Vec::new();
+        input.read_to_end(&mut bytes)?;
+        let mut decoder = Decoder::new(&bytes);
+
+        if decoder.bytes(SNAPSHOT_MAGIC.len())? != SNAPSHOT_MAGIC {
+            return Err(Error::InvalidSnapshot("not a snapshot".to_string()));
+        }
+        let version = decoder.u16()?;
+        if version != SNAPSHOT_VERSION {
+            return Err(Error::InvalidSnapshot(format!(
+                "unsupported version {version}"
+            )));
+        }
+        let cycles = decoder.u64()?;
+        let trap_mode = match decoder.u8()? {
+            0 => TrapMode::Native,
+            1 => TrapMode::Vectored,
+            mode => {
+                return Err(Error::InvalidSnapshot(format!("unknown trap mode {mode}")));
+            }
+        };
+        let mut registers = [0; 12];
+        for register in &mut registers {
+            *register = decoder.u16()?;
+        }
+        let memory = (0..MEMORY_SIZE)
+            .map(|_| decoder.u16())
+            .collect::<Result<_, _>>()?;
+        let devices = (0..decoder.u16()?)
+            .map(|_| {
+                let length = decoder.u32()? as usize;
+                decoder.bytes(length).map(<[u8]>::to_vec)
+            })
+            .collect::<Result<_, _>>()?;
+        decoder.finish()?;
+
+        Ok(Self {
+            cycles,
+            trap_mode,
+            registers,
+            memory,
+            devices,
+        })
+    }
+}
+
+/// Reads the big endian fields of a snapshot or of a device state.
+pub(crate) struct Decoder<'a>(&'a [u8]);
+
+impl<'a> Decoder<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self(bytes)
+    }
+
+    pub(crate) fn bytes(&mut self, count: usize) -> Result<&'a [u8], Error> {
+        if self.0.len() < count {
+            return Err(Error::InvalidSnapshot("truncated".to_string()));
+        }
+        let (bytes, rest) = self.0.split_at(count);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        Ok(self.bytes(N)?.try_into().expect("N bytes"))
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_be_bytes(self.array()?))
+    }
+
+    /// Fails unless everything was read.
+    pub(crate) fn finish(self) -> Result<(), Error> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidSnapshot("trailing data".to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lc3::{error::Error, machine::LittleComputer3};
+
+    use super::Snapshot;
+
+    fn file() -> Vec<u8> {
+        let mut file = Vec::new();
+        LittleComputer3::default()
+            .snapshot()
+            .write_to(&mut file)
+            .unwrap();
+        file
+    }
+
+    #[test]
+    fn test_invalid_file() {
+        let read = |bytes: &[u8]| match Snapshot::read_from(bytes) {
+            Err(Error::InvalidSnapshot(reason)) => reason,
+            other => panic!("unexpected {other:?}"),
+        };
+
+        let mut bytes = file();
+        bytes[0] = b'X';
+        assert_eq!("not a snapshot", read(&bytes));
+
+        let mut bytes = file();
+        bytes[5] = 2;
+        assert_eq!("unsupported version 2", read(&bytes));
+
+        let bytes = file();
+        assert_eq!("truncated", read(&bytes[..bytes.len() - 1]));
+
+        let mut bytes = file();
+        bytes.push(0);
+        assert_eq!("trailing data", read(&bytes));
+    }
+}
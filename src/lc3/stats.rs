@@ -0,0 +1,157 @@
+use std::fmt::Display;
+
+use super::{
+    instructions::{Instructions, OPCODES},
+    machine::StepOutcome,
+    memory::AccessKind,
+};
+
+/// Estimates the clock cycles of each instruction from the LC-3 state
+/// machine: every state takes one cycle, except the states accessing memory,
+/// which take `memory_latency` cycles. The states besides memory accesses
+/// are:
+///
+/// | instruction             | states                              |
+/// |-------------------------|-------------------------------------|
+/// | every instruction       | 18, 35, 32 to fetch and decode      |
+/// | ADD, AND, NOT, LEA, JMP | 1, 5, 9, 14, 12                     |
+/// | BR                      | 0, and 22 if the branch is taken    |
+/// | JSR, JSRR               | 4, 21 or 20                         |
+/// | LD, LDR, LDI            | 2, 27 / 6, 27 / 10, 26, 27          |
+/// | ST, STR, STI            | 3, 23 / 7, 23 / 11, 31, 23          |
+/// | TRAP                    | 15, 30                              |
+/// | RTI                     | 8, 38, 39, 42, 34                   |
+/// | interrupt               | 49, 37, 43, 47, 50, 54              |
+///
+/// Memory accesses are taken from the step, so they include those of
+/// natively executed service routines, whose instructions are not counted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CostModel {
+    pub memory_latency: u32,
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        Self { memory_latency: 5 }
+    }
+}
+
+impl CostModel {
+    /// The clock cycles `step` took.
+    pub fn cycles(&self, step: &StepOutcome) -> u64 {
+        let states = match step.instruction {
+            Instructions::Add { .. }
+            | Instructions::And { .. }
+            | Instructions::Not { .. }
+            | Instructions::LoadEffectiveAddress { .. }
+            | Instructions::Jump { .. }
+            | Instructions::RES => 1,
+            Instructions::Branch { .. } if step.pc_after != step.pc_before.wrapping_add(1) => 2,
+            Instructions::Branch { .. } => 1,
+            Instructions::JumpRegister(_)
+            | Instructions::Load { .. }
+            | Instructions::LoadRegister { .. }
+            | Instructions::Store { .. }
+            | Instructions::StoreRegister { .. }
+            | Instructions::Trap(_) => 2,
+            Instructions::LoadIndirect { .. } | Instructions::StoreIndirect { .. } => 3,
+            Instructions::RTI => 5,
+        };
+        let interrupt = if step.interrupt.is_some() { 6 } else { 0 };
+        let accesses = step.accesses.len() as u64 * self.memory_latency as u64;
+        3 + states + interrupt + accesses
+    }
+}
+
+/// Counts the work a machine did, see
+/// [`LittleComputer3::stats`](super::machine::LittleComputer3::stats).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    instructions: u64,
+    opcodes: [u64; 16],
+    reads: u64,
+    writes: u64,
+    interrupts: u64,
+    cost_model: Option<CostModel>,
+    clock_cycles: u64,
+}
+
+impl Stats {
+    /// Also estimates clock cycles with `cost_model`.
+    pub fn with_cost_model(cost_model: CostModel) -> Self {
+        Self {
+            cost_model: Some(cost_model),
+            ..Self::default()
+        }
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    /// How often the opcode, e.g. `ADD` or `TRAP`, was executed.
+    pub fn opcode(&self, name: &str) -> Option<u64> {
+        let opcode = OPCODES
+            .iter()
+            .position(|opcode| opcode.eq_ignore_ascii_case(name))?;
+        Some(self.opcodes[opcode])
+    }
+
+    /// Memory reads besides instruction fetches.
+    pub fn reads(&self) -> u64 {
+        self.reads
+    }
+
+    pub fn writes(&self) -> u64 {
+        self.writes
+    }
+
+    pub fn interrupts(&self) -> u64 {
+        self.interrupts
+    }
+
+    /// The estimated clock cycles, `None` without a cost model.
+    pub fn clock_cycles(&self) -> Option<u64> {
+        self.cost_model.map(|_| self.clock_cycles)
+    }
+
+    pub(crate) fn record(&mut self, step: &StepOutcome) {
+        self.instructions += 1;
+        for access in &step.accesses {
+            match access.kind {
+                AccessKind::Fetch => self.opcodes[(access.value >> 12) as usize] += 1,
+                AccessKind::Read => self.reads += 1,
+                AccessKind::Write => self.writes += 1,
+            }
+        }
+        if step.interrupt.is_some() {
+            self.interrupts += 1;
+        }
+        if let Some(cost_model) = &self.cost_model {
+            self.clock_cycles += cost_model.cycles(step);
+        }
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "instructions  {}", self.instructions)?;
+        if let Some(cost_model) = &self.cost_model {
+            writeln!(
+                f,
+                "clock cycles  {} (memory latency {})",
+                self.clock_cycles, cost_model.memory_latency
+            )?;
+        }
+        writeln!(f, "memory reads  {}", self.reads)?;
+        writeln!(f, "memory writes {}", self.writes)?;
+        writeln!(f, "interrupts    {}", self.interrupts)?;
+        for (name, count) in OPCODES.iter().zip(self.opcodes) {
+            if count > 0 {
+                let share = 100.0 * count as f64 / self.instructions as f64;
+                writeln!(f, "  {name:<5} {count:>10} {share:>6.1}%")?;
+            }
+        }
+        Ok(())
+    }
+}
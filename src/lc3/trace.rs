@@ -0,0 +1,372 @@
+use std::{io::Write, ops::RangeInclusive, str::FromStr};
+
+use serde_json::{json, Map, Value};
+
+use crate::vm::registers::RegistersTrait;
+
+use super::{
+    asm::parse_number,
+    error::Error,
+    instructions::OPCODES,
+    machine::StepOutcome,
+    memory::AccessKind,
+    registers::{Registers, RegistersEnum},
+};
+
+/// Starts every binary trace, followed by the format version.
+pub const BINARY_MAGIC: &[u8; 4] = b"LC3T";
+pub const BINARY_VERSION: u8 = 1;
+
+/// Registers whose changes are recorded, in the order of the binary mask.
+const REGISTERS: [(&str, RegistersEnum); 11] = [
+    ("R0", RegistersEnum::R0),
+    ("R1", RegistersEnum::R1),
+    ("R2", RegistersEnum::R2),
+    ("R3", RegistersEnum::R3),
+    ("R4", RegistersEnum::R4),
+    ("R5", RegistersEnum::R5),
+    ("R6", RegistersEnum::R6),
+    ("R7", RegistersEnum::R7),
+    ("PSR", RegistersEnum::ProcessorStatus),
+    ("USP", RegistersEnum::SavedUserStackPointer),
+    ("SSP", RegistersEnum::SavedSupervisorStackPointer),
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// One JSON object per line.
+    #[default]
+    JsonLines,
+    /// [`BINARY_MAGIC`] and [`BINARY_VERSION`], then per record, little
+    /// endian: cycle (u64), PC, word and PSR (u16 each), a u16 mask of the
+    /// changed registers R0-R7, PSR, USP and SSP followed by their new values,
+    /// and a u16 count of memory accesses, each a kind byte (0 read, 1 write),
+    /// the address, for writes the old value, and the value.
+    Binary,
+}
+
+impl FromStr for TraceFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "jsonl" => Ok(TraceFormat::JsonLines),
+            "binary" => Ok(TraceFormat::Binary),
+            _ => Err(format!("unknown trace format '{format}'")),
+        }
+    }
+}
+
+/// Selects the instructions that are traced; by default all of them.
+#[derive(Clone, Debug, Default)]
+pub struct TraceFilter {
+    range: Option<RangeInclusive<u16>>,
+    opcodes: Vec<u16>,
+}
+
+impl TraceFilter {
+    /// Only traces instructions whose address lies in `range`.
+    pub fn range(mut self, range: RangeInclusive<u16>) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    /// Only traces instructions with the given opcodes, e.g. `ADD` or `TRAP`.
+    /// `None` if a name is not an opcode.
+    pub fn opcodes<'a>(mut self, names: impl IntoIterator<Item = &'a str>) -> Option<Self> {
+        for name in names {
+            let opcode = OPCODES
+                .iter()
+                .position(|opcode| opcode.eq_ignore_ascii_case(name))?;
+            self.opcodes.push(opcode as u16);
+        }
+        Some(self)
+    }
+
+    fn matches(&self, pc: u16, word: u16) -> bool {
+        self.range.as_ref().is_none_or(|range| range.contains(&pc))
+            && (self.opcodes.is_empty() || self.opcodes.contains(&(word >> 12)))
+    }
+}
+
+/// Parses an address range like `x3000..x30FF`; a single address is a range
+/// of one.
+pub fn parse_range(text: &str) -> Option<RangeInclusive<u16>> {
+    let address = |text: &str| {
+        let number = parse_number(text)?;
+        u16::try_from(number).ok()
+    };
+    match text.split_once("..") {
+        Some((start, end)) => Some(address(start)?..=address(end)?),
+        None => address(text).map(|address| address..=address),
+    }
+}
+
+/// Writes one record per retired instruction. Attach it to a machine with
+/// [`LittleComputer3Builder::tracer`](super::machine::LittleComputer3Builder::tracer).
+pub struct Tracer {
+    output: Box<dyn Write>,
+    format: TraceFormat,
+    filter: TraceFilter,
+}
+
+impl Tracer {
+    pub fn new<W>(mut output: W, format: TraceFormat) -> Result<Self, Error>
+    where
+        W: Write + 'static,
+    {
+        if format == TraceFormat::Binary {
+            output.write_all(BINARY_MAGIC)?;
+            output.write_all(&[BINARY_VERSION])?;
+        }
+        Ok(Self {
+            output: Box::new(output),
+            format,
+            filter: TraceFilter::default(),
+        })
+    }
+
+    pub fn filter(mut self, filter: TraceFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Records the step that was the `cycle`th instruction, given the
+    /// registers before and after it.
+    pub(crate) fn record(
+        &mut self,
+        cycle: u64,
+        before: &Registers,
+        after: &Registers,
+        step: &StepOutcome,
+    ) -> Result<(), Error> {
+        let word = step
+            .accesses
+            .iter()
+            .find(|access| access.kind == AccessKind::Fetch)
+            .map_or(0, |access| access.value);
+        if self.filter.matches(step.pc_before, word) {
+            match self.format {
+                TraceFormat::JsonLines => self.json(cycle, word, before, after, step)?,
+                TraceFormat::Binary => self.binary(cycle, word, before, after, step)?,
+            }
+        }
+        if step.halted {
+            self.output.flush()?;
+        }
+        Ok(())
+    }
+
+    fn json(
+        &mut self,
+        cycle: u64,
+        word: u16,
+        before: &Registers,
+        after: &Registers,
+        step: &StepOutcome,
+    ) -> Result<(), Error> {
+        let registers: Map<String, Value> = changed(before, after)
+            .map(|(index, value)| (REGISTERS[index].0.to_string(), json!(value)))
+            .collect();
+        let reads: Vec<_> = step
+            .accesses
+            .iter()
+            .filter(|access| access.kind == AccessKind::Read)
+            .map(|access| json!({ "address": access.address, "value": access.value }))
+            .collect();
+        let writes: Vec<_> = step
+            .accesses
+            .iter()
+            .filter(|access| access.kind == AccessKind::Write)
+            .map(|access| {
+                json!({ "address": access.address, "old": access.old, "value": access.value })
+            })
+            .collect();
+        let condition = after.get(RegistersEnum::Condition);
+        let cc: String = [(0b100, 'N'), (0b010, 'Z'), (0b001, 'P')]
+            .iter()
+            .filter(|(bit, _)| condition & bit != 0)
+            .map(|(_, flag)| *flag)
+            .collect();
+
+        let mut record = json!({
+            "cycle": cycle,
+            "pc": step.pc_before,
+            "word": word,
+            "instruction": step.instruction.at(step.pc_before).to_string(),
+            "registers": registers,
+            "reads": reads,
+            "writes": writes,
+            "cc": cc,
+        });
+        if let Some(interrupt) = &step.interrupt {
+            record["interrupt"] = json!(interrupt.vector);
+        }
+        writeln!(self.output, "{record}")?;
+        Ok(())
+    }
+
+    fn binary(
+        &mut self,
+        cycle: u64,
+        word: u16,
+        before: &Registers,
+        after: &Registers,
+        step: &StepOutcome,
+    ) -> Result<(), Error> {
+        let mut record = Vec::with_capacity(32);
+        record.extend(cycle.to_le_bytes());
+        record.extend(step.pc_before.to_le_bytes());
+        record.extend(word.to_le_bytes());
+        record.extend(after.get(RegistersEnum::ProcessorStatus).to_le_bytes());
+
+        let mut mask = 0u16;
+        let mut values = Vec::new();
+        for (index, value) in changed(before, after) {
+            mask |= 1 << index;
+            values.extend(value.to_le_bytes());
+        }
+        record.extend(mask.to_le_bytes());
+        record.extend(values);
+
+        let accesses: Vec<_> = step
+            .accesses
+            .iter()
+            .filter(|access| access.kind != AccessKind::Fetch)
+            .collect();
+        record.extend((accesses.len() as u16).to_le_bytes());
+        for access in accesses {
+            let write = access.kind == AccessKind::Write;
+            record.push(write as u8);
+            record.extend(access.address.to_le_bytes());
+            if write {
+                record.extend(access.old.to_le_bytes());
+            }
+            record.extend(access.value.to_le_bytes());
+        }
+        self.output.write_all(&record)?;
+        Ok(())
+    }
+}
+
+/// The index into [`REGISTERS`] and the new value of each changed register.
+fn changed<'a>(
+    before: &'a Registers,
+    after: &'a Registers,
+) -> impl Iterator<Item = (usize, u16)> + 'a {
+    REGISTERS
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, register))| before.get(*register) != after.get(*register))
+        .map(|(index, (_, register))| (index, after.get(*register)))
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use serde_json::{json, Value};
+
+    use crate::lc3::{asm::assemble, console::Shared, machine::LittleComputer3};
+
+    use super::{parse_range, TraceFilter, TraceFormat, Tracer, BINARY_MAGIC, BINARY_VERSION};
+
+    const SOURCE: &str = "
+        .ORIG x3000
+        LD R0, DATA
+        ADD R0, R0, #1
+        ST R0, DATA
+        HALT
+DATA    .FILL #4
+";
+
+    fn trace(format: TraceFormat, filter: TraceFilter) -> Vec<u8> {
+        let output = Shared::new(Vec::new());
+        let tracer = Tracer::new(output.clone(), format).unwrap().filter(filter);
+        let mut lc3 = LittleComputer3::builder()
+            .input(Cursor::new([]))
+            .output(Vec::new())
+            .tracer(tracer)
+            .build()
+            .unwrap();
+        let program = assemble(SOURCE).unwrap();
+        lc3.load_program(Cursor::new(program.to_bytes())).unwrap();
+        lc3.execute_program().unwrap();
+        let trace = output.borrow().clone();
+        trace
+    }
+
+    fn records(filter: TraceFilter) -> Vec<Value> {
+        let trace = trace(TraceFormat::JsonLines, filter);
+        String::from_utf8(trace)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let records = records(TraceFilter::default());
+        assert_eq!(4, records.len());
+        assert_eq!(
+            json!({
+                "cycle": 1,
+                "pc": 0x3000,
+                "word": 0x2003,
+                "instruction": "LD R0, x3004",
+                "registers": { "R0": 4, "PSR": 0x8001 },
+                "reads": [{ "address": 0x3004, "value": 4 }],
+                "writes": [],
+                "cc": "P",
+            }),
+            records[0]
+        );
+        assert_eq!(
+            json!([{ "address": 0x3004, "old": 4, "value": 5 }]),
+            records[2]["writes"]
+        );
+        assert_eq!(json!({}), records[2]["registers"]);
+        assert_eq!("TRAP x25 ; HALT", records[3]["instruction"]);
+    }
+
+    #[test]
+    fn test_filter() {
+        let filter = TraceFilter::default().opcodes(["ld", "ST"]).unwrap();
+        let pcs: Vec<_> = records(filter)
+            .iter()
+            .map(|record| record["pc"].clone())
+            .collect();
+        assert_eq!(vec![json!(0x3000), json!(0x3002)], pcs);
+
+        let filter = TraceFilter::default().range(0x3001..=0x3003);
+        assert_eq!(3, records(filter).len());
+
+        assert!(TraceFilter::default().opcodes(["MOV"]).is_none());
+    }
+
+    #[test]
+    fn test_binary() {
+        let trace = trace(
+            TraceFormat::Binary,
+            TraceFilter::default().range(0x3000..=0x3000),
+        );
+        let mut expected = BINARY_MAGIC.to_vec();
+        expected.push(BINARY_VERSION);
+        expected.extend(1u64.to_le_bytes());
+        expected.extend([0x00, 0x30, 0x03, 0x20, 0x01, 0x80]);
+        // R0 and PSR changed
+        expected.extend([0x01, 0x01, 0x04, 0x00, 0x01, 0x80]);
+        // one read of x3004
+        expected.extend([0x01, 0x00, 0x00, 0x04, 0x30, 0x04, 0x00]);
+        assert_eq!(expected, trace);
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(Some(0x3000..=0x30FF), parse_range("x3000..x30FF"));
+        assert_eq!(Some(0x3000..=0x3000), parse_range("x3000"));
+        assert_eq!(None, parse_range("x3000..LOOP"));
+        assert_eq!(None, parse_range("x10000"));
+    }
+}
@@ -1,7 +1,57 @@
-use lc3::lc3::machine::LittleComputer3;
+use lc3::{
+    lc3::{
+        asm,
+        console::{BlockingReader, Hotkey, HotkeyReader, NonBlockingReader, Shared},
+        dap,
+        debugger::Debugger,
+        disasm, gdbstub,
+        keyboard::EofPolicy,
+        machine::{LittleComputer3, RunOutcome},
+        profiler::Profiler,
+        registers::RegistersEnum,
+        replay::Recording,
+        snapshot::Snapshot,
+        stats::{CostModel, Stats},
+        trace::{self, TraceFilter, TraceFormat, Tracer},
+    },
+    vm::registers::RegistersTrait,
+};
+use std::{
+    collections::BTreeMap,
+    io::{BufReader, BufWriter, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
 use termios::*;
 
-fn init_terminal() -> Result<Termios, std::io::Error> {
+/// Instructions the debugger can undo unless `--history` says otherwise.
+const DEFAULT_HISTORY: usize = 100_000;
+/// Ctrl-T saves the machine state while a program runs with `--save-state`.
+const SAVE_STATE_KEY: u8 = 0x14;
+
+/// Pressed on SIGINT, so Ctrl-C in the debugger pauses the program instead of
+/// ending the emulator.
+static INTERRUPT: OnceLock<Hotkey> = OnceLock::new();
+
+extern "C" fn on_interrupt(_signal: libc::c_int) {
+    if let Some(interrupt) = INTERRUPT.get() {
+        interrupt.press();
+    }
+}
+
+fn catch_interrupt() -> Hotkey {
+    let interrupt = INTERRUPT.get_or_init(Hotkey::default).clone();
+    let handler: extern "C" fn(libc::c_int) = on_interrupt;
+    // SAFETY: the handler only stores to an atomic flag
+    unsafe { libc::signal(libc::SIGINT, handler as libc::sighandler_t) };
+    interrupt
+}
+
+fn init_terminal() -> Result<Option<Termios>, std::io::Error> {
+    // piped input has no terminal to configure
+    if !std::io::stdin().is_terminal() {
+        return Ok(None);
+    }
     let termios = termios::Termios::from_fd(0)?;
 
     let mut new_termios = termios;
@@ -10,48 +60,375 @@ fn init_terminal() -> Result<Termios, std::io::Error> {
 
     tcsetattr(0, TCSANOW, &new_termios)?;
 
-    Ok(termios)
+    Ok(Some(termios))
 }
 
-fn restore_terminal(termios: Termios) -> std::io::Result<()> {
-    tcsetattr(0, TCSANOW, &termios)
+fn restore_terminal(termios: Option<Termios>) -> std::io::Result<()> {
+    match termios {
+        Some(termios) => tcsetattr(0, TCSANOW, &termios),
+        None => Ok(()),
+    }
 }
 
 fn usage() {
-    println!("Usage: lc3 [--debug] path/to/program");
+    println!("Usage: lc3 [options] path/to/program.obj|path/to/source.asm");
+    println!("       lc3 [options] --load-state path/to/state [path/to/source.asm]");
+    println!("       lc3 asm path/to/source.asm [-o path/to/program.obj]");
+    println!("       lc3 disasm path/to/program.obj");
+    println!("       lc3 dap");
+    println!();
+    println!(
+        "  --debug                   start the interactive debugger, 'help' lists its commands"
+    );
+    println!("  --history <n>             let the debugger undo up to n instructions (default {DEFAULT_HISTORY})");
+    println!("  --gdb <address>           wait for gdb to connect on address, e.g. 127.0.0.1:1234");
+    println!("  --os                      run TRAPs through the bundled LC-3 operating system");
+    println!("  --display-latency <n>     keep the display busy for n instructions per character");
+    println!("  --on-eof <policy>         halt, error, eot or xffff once the input is exhausted");
+    println!(
+        "  --stats                   print instruction counts and estimated clock cycles at exit"
+    );
+    println!(
+        "  --profile <file>          write the most executed addresses and subroutines to file"
+    );
+    println!(
+        "  --profile-folded <file>   write the executed call stacks to file for flamegraph tools"
+    );
+    println!("  --exit-code               exit with the low byte of R0 once the program halts");
+    println!("  --save-state <file>       save the machine state to file when Ctrl-T is pressed");
+    println!(
+        "  --load-state <file>       continue from a saved state instead of loading a program"
+    );
+    println!("  --record-input <file>     log every input byte with the instruction count it was read at");
+    println!("  --replay-input <file>     feed the input of a recording instead of the terminal");
+    println!("  --trace <file>            record every executed instruction to file");
+    println!("  --trace-format <format>   jsonl (default) or binary");
+    println!("  --trace-range <a..b>      only trace instructions at addresses a to b");
+    println!("  --trace-ops <ops>         only trace these opcodes, e.g. ADD,LDR,TRAP");
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let termios = init_terminal()?;
+#[derive(Default)]
+struct Options {
+    file: Option<String>,
+    debug: bool,
+    history: Option<usize>,
+    gdb: Option<String>,
+    exit_code: bool,
+    stats: bool,
+    profile: Option<String>,
+    profile_folded: Option<String>,
+    os: bool,
+    display_latency: u32,
+    eof_policy: EofPolicy,
+    save_state: Option<String>,
+    load_state: Option<String>,
+    record_input: Option<String>,
+    replay_input: Option<String>,
+    trace: Option<String>,
+    trace_format: TraceFormat,
+    trace_filter: TraceFilter,
+}
+
+fn parse_eof_policy(policy: &str) -> Option<EofPolicy> {
+    match policy {
+        "halt" => Some(EofPolicy::Halt),
+        "error" => Some(EofPolicy::Error),
+        "eot" => Some(EofPolicy::Value(0x0004)),
+        "xffff" => Some(EofPolicy::Value(0xFFFF)),
+        _ => None,
+    }
+}
 
-    let args: Vec<String> = std::env::args().collect();
-    let (file, debug) = match args.len() {
-        2 => (args[1].clone(), false),
-        3 => {
-            if args[1] == "--debug" {
-                (args[2].clone(), true)
-            } else {
-                return {
-                    usage();
-                    Ok(())
-                };
+fn parse_options(mut args: impl Iterator<Item = String>) -> Option<Options> {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--debug" => options.debug = true,
+            "--history" => options.history = Some(args.next()?.parse().ok()?),
+            "--gdb" => options.gdb = Some(args.next()?),
+            "--exit-code" => options.exit_code = true,
+            "--stats" => options.stats = true,
+            "--profile" => options.profile = Some(args.next()?),
+            "--profile-folded" => options.profile_folded = Some(args.next()?),
+            "--os" => options.os = true,
+            "--display-latency" => options.display_latency = args.next()?.parse().ok()?,
+            "--on-eof" => options.eof_policy = parse_eof_policy(&args.next()?)?,
+            "--save-state" => options.save_state = Some(args.next()?),
+            "--load-state" => options.load_state = Some(args.next()?),
+            "--record-input" => options.record_input = Some(args.next()?),
+            "--replay-input" => options.replay_input = Some(args.next()?),
+            "--trace" => options.trace = Some(args.next()?),
+            "--trace-format" => options.trace_format = args.next()?.parse().ok()?,
+            "--trace-range" => {
+                let range = trace::parse_range(&args.next()?)?;
+                options.trace_filter = options.trace_filter.range(range);
+            }
+            "--trace-ops" => {
+                let opcodes = args.next()?;
+                options.trace_filter = options.trace_filter.opcodes(opcodes.split(','))?;
             }
+            _ if options.file.is_none() && !arg.starts_with("--") => options.file = Some(arg),
+            _ => return None,
+        }
+    }
+    if options.file.is_none() && options.load_state.is_none() {
+        return None;
+    }
+    if options.record_input.is_some() && options.replay_input.is_some() {
+        return None;
+    }
+    Some(options)
+}
+
+fn assemble(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut source = None;
+    let mut output = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => output = args.next().map(PathBuf::from),
+            _ if source.is_none() => source = Some(PathBuf::from(arg)),
+            _ => source = None,
+        }
+    }
+    let Some(source) = source else {
+        usage();
+        return Ok(());
+    };
+
+    let program = assemble_source(&source)?;
+    let output = output.unwrap_or_else(|| source.with_extension("obj"));
+    std::fs::write(output, program.to_bytes())?;
+    Ok(())
+}
+
+/// Assembles `source`, exiting with every diagnostic if that fails.
+fn assemble_source(source: &Path) -> Result<asm::Program, Box<dyn std::error::Error>> {
+    match asm::assemble(&std::fs::read_to_string(source)?) {
+        Ok(program) => Ok(program),
+        Err(diagnostics) => {
+            for diagnostic in diagnostics {
+                eprintln!("{}:{}", source.display(), diagnostic);
+            }
+            std::process::exit(1);
+        }
+    }
+}
+
+type Symbols = BTreeMap<String, u16>;
+
+/// Reads an object file, or assembles the program first when given its
+/// source, which also provides the symbol table.
+fn read_program(path: &Path) -> Result<(Vec<u8>, Symbols), Box<dyn std::error::Error>> {
+    if path.extension().is_some_and(|extension| extension == "asm") {
+        let program = assemble_source(path)?;
+        Ok((program.to_bytes(), program.symbols().clone()))
+    } else {
+        Ok((std::fs::read(path)?, Symbols::new()))
+    }
+}
+
+/// Restores the state saved at `--load-state` if given, otherwise loads the
+/// program.
+fn load(
+    lc3: &mut LittleComputer3,
+    program: &[u8],
+    load_state: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match load_state {
+        Some(path) => lc3.restore(&Snapshot::read_from(std::fs::File::open(path)?)?)?,
+        None => lc3.load_program(program)?,
+    }
+    Ok(())
+}
+
+/// Opens the `--record-input` log and reads the `--replay-input` recording.
+fn open_input_logs(
+    options: &Options,
+) -> Result<(Option<std::fs::File>, Option<Recording>), Box<dyn std::error::Error>> {
+    let log = match &options.record_input {
+        Some(path) => Some(std::fs::File::create(path)?),
+        None => None,
+    };
+    let recording = match &options.replay_input {
+        Some(path) => Some(Recording::read_from(std::fs::File::open(path)?)?),
+        None => None,
+    };
+    Ok((log, recording))
+}
+
+/// Records the input to `--record-input` or replaces it with the recording
+/// at `--replay-input`.
+fn log_input(
+    lc3: &mut LittleComputer3,
+    (log, recording): (Option<std::fs::File>, Option<Recording>),
+) {
+    if let Some(log) = log {
+        lc3.record_input(log);
+    }
+    if let Some(recording) = recording {
+        lc3.replay_input(recording);
+    }
+}
+
+/// Prints the `--stats` summary and writes the `--profile` reports.
+fn report(
+    lc3: &LittleComputer3,
+    options: &Options,
+    symbols: &Symbols,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(stats) = lc3.stats() {
+        eprint!("{stats}");
+    }
+    if let Some(profiler) = lc3.profiler() {
+        if let Some(path) = &options.profile {
+            let mut output = BufWriter::new(std::fs::File::create(path)?);
+            profiler.write_report(symbols, &mut output)?;
+            output.flush()?;
         }
-        _ => {
-            return {
-                usage();
-                Ok(())
-            };
+        if let Some(path) = &options.profile_folded {
+            let mut output = BufWriter::new(std::fs::File::create(path)?);
+            profiler.write_folded(symbols, &mut output)?;
+            output.flush()?;
         }
+    }
+    Ok(())
+}
+
+fn save_state(lc3: &LittleComputer3, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut output = BufWriter::new(std::fs::File::create(path)?);
+    lc3.snapshot().write_to(&mut output)?;
+    output.flush()?;
+    Ok(())
+}
+
+fn disassemble(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn std::error::Error>> {
+    let (Some(file), None) = (args.next(), args.next()) else {
+        usage();
+        return Ok(());
     };
 
-    let file = std::fs::File::open(file)?;
+    let mut stdout = std::io::stdout().lock();
+    for line in disasm::disassemble_object(std::fs::File::open(file)?)? {
+        writeln!(stdout, "{line}")?;
+    }
+    Ok(())
+}
 
-    let mut lc3 = LittleComputer3::default();
-    lc3.load_program(file)?;
-    lc3.execute_program(debug)?;
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1).peekable();
+    if args.next_if(|arg| arg == "asm").is_some() {
+        return assemble(args);
+    }
+    if args.next_if(|arg| arg == "disasm").is_some() {
+        return disassemble(args);
+    }
+    if args.next_if(|arg| arg == "dap").is_some() {
+        // the client launches the program, so there is nothing else to parse
+        return Ok(dap::serve(
+            BufReader::new(std::io::stdin()),
+            std::io::stdout(),
+        )?);
+    }
+
+    let Some(options) = parse_options(args) else {
+        usage();
+        return Ok(());
+    };
+
+    // with a saved state the program only provides the symbols
+    let (program, symbols) = match &options.file {
+        Some(file) => read_program(Path::new(file))?,
+        None => (Vec::new(), Symbols::new()),
+    };
+    let load_state = options.load_state.as_deref();
+
+    let mut builder = LittleComputer3::builder()
+        .eof_policy(options.eof_policy)
+        .display_latency(options.display_latency);
+    if options.os {
+        builder = builder.os();
+    }
+    if options.stats {
+        builder = builder.stats(Stats::with_cost_model(CostModel::default()));
+    }
+    if options.profile.is_some() || options.profile_folded.is_some() {
+        builder = builder.profiler(Profiler::new());
+    }
+    if let Some(path) = &options.trace {
+        let output = BufWriter::new(std::fs::File::create(path)?);
+        let tracer =
+            Tracer::new(output, options.trace_format)?.filter(options.trace_filter.clone());
+        builder = builder.tracer(tracer);
+    }
+
+    if options.debug {
+        // The program and the debugger share stdin: the program gets what is
+        // typed while it runs, the debugger reads its commands line by line at
+        // the prompt. The terminal stays canonical.
+        let stdin = Shared::new(NonBlockingReader::new(std::io::stdin()));
+        let mut lc3 = builder
+            .input(stdin.clone())
+            .history(options.history.unwrap_or(DEFAULT_HISTORY))
+            .pause(catch_interrupt())
+            .build()?;
+        load(&mut lc3, &program, load_state)?;
+        log_input(&mut lc3, open_input_logs(&options)?);
+        let mut debugger = Debugger::with_symbols(symbols.clone());
+        debugger.run(
+            &mut lc3,
+            BufReader::with_capacity(1, BlockingReader::new(stdin)),
+            std::io::stdout(),
+        )?;
+        report(&lc3, &options, &symbols)?;
+        return Ok(());
+    }
+
+    let stdin: Box<dyn Read + Send> = match &options.save_state {
+        Some(_) => {
+            let (stdin, hotkey) = HotkeyReader::new(std::io::stdin(), SAVE_STATE_KEY);
+            // also stops a TRAP waiting for a key, so no later input is saved
+            builder = builder.pause(hotkey);
+            Box::new(stdin)
+        }
+        None => Box::new(std::io::stdin()),
+    };
+    let mut lc3 = builder.build()?;
+    load(&mut lc3, &program, load_state)?;
+    let input_logs = open_input_logs(&options)?;
+    let termios = init_terminal()?;
+    // the reader starts reading right away, so only once the terminal is raw
+    lc3.set_input(NonBlockingReader::new(stdin));
+    log_input(&mut lc3, input_logs);
+
+    if let Some(address) = &options.gdb {
+        eprintln!("waiting for gdb on {address}\r");
+        let served = gdbstub::listen(address.as_str(), &mut lc3);
+        restore_terminal(termios)?;
+        report(&lc3, &options, &symbols)?;
+        return Ok(served?);
+    }
+
+    let outcome = match &options.save_state {
+        Some(path) => loop {
+            match lc3.execute_program() {
+                Ok(RunOutcome::Paused { .. }) => match save_state(&lc3, path) {
+                    Ok(()) => eprintln!("state saved to {path}\r"),
+                    Err(e) => eprintln!("could not save state to {path}: {e}\r"),
+                },
+                outcome => break outcome,
+            }
+        },
+        None => lc3.execute_program(),
+    };
 
     restore_terminal(termios)?;
+    report(&lc3, &options, &symbols)?;
+
+    if let RunOutcome::Halted { .. } = outcome? {
+        if options.exit_code {
+            std::process::exit((lc3.registers().get(RegistersEnum::R0) & 0xFF) as i32);
+        }
+    }
 
     Ok(())
 }
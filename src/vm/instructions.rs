@@ -1,6 +1,12 @@
 use super::{memory::MemoryTrait, registers::RegistersTrait};
 use std::io::{Read, Write};
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExecutionState {
+    Running,
+    Halted,
+}
+
 pub trait InstructionsTrait {
     type ValueType;
     type InstructionSet;
@@ -13,7 +19,7 @@ pub trait InstructionsTrait {
         memory: &mut M,
         input: &mut I,
         output: &mut O,
-    ) -> Result<(), Self::Error>
+    ) -> Result<ExecutionState, Self::Error>
     where
         R: RegistersTrait<ValueType = Self::ValueType, RegisterSet = Self::RegisterSet>,
         M: MemoryTrait<ValueType = Self::ValueType>,
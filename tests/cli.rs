@@ -0,0 +1,41 @@
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+/// Runs a program echoing its input until the input ends.
+fn echo(name: &str, input: &[u8]) -> Output {
+    let source = std::env::temp_dir().join(format!("lc3-{name}-{}.asm", std::process::id()));
+    let program = ".ORIG x3000\nLOOP GETC\nOUT\nADD R0, R0, #0\nBRp LOOP\nHALT\n.END\n";
+    std::fs::write(&source, program).unwrap();
+
+    let mut lc3 = Command::new(env!("CARGO_BIN_EXE_lc3"))
+        .args(["--on-eof", "halt"])
+        .arg(&source)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    lc3.stdin.take().unwrap().write_all(input).unwrap();
+    let output = lc3.wait_with_output().unwrap();
+    std::fs::remove_file(&source).unwrap();
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+#[test]
+fn test_piped_input() {
+    assert_eq!(b"abc", echo("getc", b"abc").stdout.as_slice());
+}
+
+#[test]
+fn test_save_state_key_without_save_state() {
+    // Ctrl-T is only taken from the input with --save-state
+    assert_eq!(b"a\x14b", echo("ctrl-t", b"a\x14b").stdout.as_slice());
+}